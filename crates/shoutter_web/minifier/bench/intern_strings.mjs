@@ -0,0 +1,24 @@
+// brotli size of `typeof v == "<lit>"` spread over glue, against the same with the literal in
+// a `const` of a one-letter name, as `InternString` and then terser would write it.
+// e.g. `node bench/intern_strings.mjs src/opt_js/corpus/glue.js`
+import fs from 'fs';
+import zlib from 'zlib';
+
+const brotli = (s) =>
+    zlib.brotliCompressSync(Buffer.from(s), {
+        params: { [zlib.constants.BROTLI_PARAM_QUALITY]: 11 },
+    }).length;
+// comments and whitespace are gone after terser
+const glue = fs.readFileSync(process.argv[2], 'utf8').replace(/\/\/.*$/gm, '').replace(/\s+/g, ' ');
+
+for (const lit of ['undefined', 'function', 'object', 'string']) {
+    for (const count of [2, 4, 8, 16]) {
+        const stmts = glue.split(';');
+        const step = Math.floor(stmts.length / (count + 1));
+        const uses = (x) =>
+            stmts.map((s, i) => (i % step === 0 && i / step < count ? `${s};typeof v==${x}` : s)).join(';');
+        const literal = brotli(uses(`"${lit}"`));
+        const interned = brotli(`const a="${lit}";` + uses('a'));
+        console.log(`${lit} x${count}: ${literal - interned} bytes saved`);
+    }
+}
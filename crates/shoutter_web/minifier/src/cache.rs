@@ -0,0 +1,110 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::report::FileReport;
+use crate::sys::fs;
+
+const MANIFEST: &str = "manifest.json";
+// the minifier itself and the node packages it calls, relative to `pkg`.
+const TOOLS: &[&str] = &["minifier_rs_bg.wasm", "../pnpm-lock.yaml"];
+
+/// Outputs of previous runs, keyed by the inputs of a target, the pipeline configuration
+/// and the build of the minifier and its node packages.
+/// Each entry is a directory holding the written files and a manifest.
+pub struct Cache {
+    dir: PathBuf,
+    base: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Entry {
+    /// every file written to the output directory, including source maps.
+    pub outputs: Vec<String>,
+    pub files: Vec<FileReport>,
+}
+
+impl Cache {
+    pub async fn new(dir: PathBuf, config: &Config) -> Result<Self> {
+        let mut hasher = Sha256::new();
+        for tool in TOOLS {
+            let content = fs::read_file(Path::new(tool))
+                .await
+                .with_context(|| format!("failed to read {tool}"))?;
+            hasher.update((content.len() as u64).to_le_bytes());
+            hasher.update(content);
+        }
+        Ok(Self {
+            dir,
+            base: format!(
+                "{} {}",
+                hex::encode(hasher.finalize()),
+                outputs_config(config)
+            ),
+        })
+    }
+
+    pub fn key(&self, inputs: &[(String, &[u8])]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.base.as_bytes());
+        for (name, content) in inputs {
+            hasher.update((name.len() as u64).to_le_bytes());
+            hasher.update(name.as_bytes());
+            hasher.update((content.len() as u64).to_le_bytes());
+            hasher.update(content);
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    /// copies outputs of a previous run into `out_dir`. `None` if there is no usable entry.
+    pub async fn restore(&self, key: &str, out_dir: &Path) -> Option<Vec<FileReport>> {
+        let dir = self.dir.join(key);
+        let manifest = fs::read_file(&dir.join(MANIFEST)).await.ok()?;
+        let entry: Entry = serde_json::from_slice(&manifest).ok()?;
+        for output in &entry.outputs {
+            fs::copy_file(&dir.join(output), &out_dir.join(output))
+                .await
+                .ok()?;
+        }
+        Some(entry.files)
+    }
+
+    pub async fn store(&self, key: &str, out_dir: &Path, entry: &Entry) -> Result<()> {
+        let dir = self.dir.join(key);
+        fs::mkdir_all(&dir).await?;
+        for output in &entry.outputs {
+            fs::copy_file(&out_dir.join(output), &dir.join(output)).await?;
+        }
+        // written last so that a half-written entry is never restored.
+        fs::write_file(
+            &dir.join(MANIFEST),
+            serde_json::to_string(entry)?.as_bytes(),
+        )
+        .await
+    }
+}
+
+// what changes outputs or their reports; where reports are written and budgets don't.
+fn outputs_config(config: &Config) -> String {
+    let Config {
+        report: _,
+        baseline: _,
+        budget: _,
+        cache_dir: _,
+        brotli,
+        precompress,
+        source_map,
+        symbol,
+        js,
+        passes,
+        keep_classes,
+        wasm_classes,
+    } = config;
+    format!(
+        "{brotli:?} {precompress:?} {source_map:?} {symbol:?} {js:?} {passes:?} {keep_classes:?} \
+         {wasm_classes:?}"
+    )
+}
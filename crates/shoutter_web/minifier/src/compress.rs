@@ -0,0 +1,146 @@
+// compression for reporting sizes and for writing precompressed copies of outputs.
+// all in Rust, as the minifier only runs as wasm under node.
+
+use std::fmt::Debug;
+use std::io::Write;
+use std::str::FromStr;
+
+use anyhow::{bail, ensure, Context, Result};
+
+pub trait Compressor: Debug {
+    /// appended to precompressed files, e.g. `br` for `index.html.br`.
+    fn extension(&self) -> &'static str;
+    fn compress(&self, src: &[u8]) -> Result<Vec<u8>>;
+    /// the settings when compressing with brotli, to reuse the output for the reported size.
+    fn as_brotli(&self) -> Option<Brotli> {
+        None
+    }
+}
+
+/// `br[:<quality>[:<window>]]`, 11 and 22 unless given, as the `brotli` npm package did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Brotli {
+    pub quality: u32,
+    /// log2 of the window size
+    pub window: u32,
+}
+
+impl Default for Brotli {
+    fn default() -> Self {
+        Self {
+            quality: 11,
+            window: 22,
+        }
+    }
+}
+
+impl Compressor for Brotli {
+    fn extension(&self) -> &'static str {
+        "br"
+    }
+
+    fn compress(&self, mut src: &[u8]) -> Result<Vec<u8>> {
+        let params = brotli::enc::BrotliEncoderParams {
+            quality: self.quality as i32,
+            lgwin: self.window as i32,
+            ..Default::default()
+        };
+        let mut compressed = vec![];
+        brotli::BrotliCompress(&mut src, &mut compressed, &params)?;
+        Ok(compressed)
+    }
+
+    fn as_brotli(&self) -> Option<Brotli> {
+        Some(*self)
+    }
+}
+
+impl FromStr for Brotli {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut brotli = Brotli::default();
+        let mut args = s.split(':');
+        ensure!(args.next() == Some("br"), "expected `br`: {s}");
+        if let Some(quality) = args.next() {
+            brotli.quality = quality.parse().context("invalid brotli quality")?;
+        }
+        if let Some(window) = args.next() {
+            brotli.window = window.parse().context("invalid brotli window")?;
+        }
+        if brotli.quality > 11 || !(10..=24).contains(&brotli.window) || args.next().is_some() {
+            bail!("brotli takes a quality of 0-11 and a window of 10-24: {s}");
+        }
+        Ok(brotli)
+    }
+}
+
+/// `gz[:<level>]`, 9 unless given.
+#[derive(Debug, Clone, Copy)]
+pub struct Gzip {
+    pub level: u32,
+}
+
+impl Compressor for Gzip {
+    fn extension(&self) -> &'static str {
+        "gz"
+    }
+
+    fn compress(&self, src: &[u8]) -> Result<Vec<u8>> {
+        let mut encoder =
+            flate2::write::GzEncoder::new(vec![], flate2::Compression::new(self.level));
+        encoder.write_all(src)?;
+        Ok(encoder.finish()?)
+    }
+}
+
+/// One of the formats above, e.g. `br:9` or `gz`.
+pub fn parse(s: &str) -> Result<Box<dyn Compressor>> {
+    let (format, level) = s.split_once(':').unwrap_or((s, ""));
+    let invalid = || format!("invalid compression level: {s}");
+    Ok(match format {
+        "br" => Box::new(s.parse::<Brotli>()?),
+        "gz" => {
+            let level = match level {
+                "" => 9,
+                level => level.parse().with_context(invalid)?,
+            };
+            ensure!(level <= 9, "gzip takes a level of 0-9: {s}");
+            Box::new(Gzip { level })
+        }
+        _ => bail!("unknown compression: {s}"),
+    })
+}
+
+#[test]
+fn round_trip() {
+    use std::io::Read;
+
+    let src = include_bytes!("opt_js/corpus/glue.js");
+    let decompressed = |compressor: &dyn Compressor| {
+        let compressed = compressor.compress(src).unwrap();
+        assert!(compressed.len() < src.len(), "{compressor:?}");
+        let mut decompressed = vec![];
+        match compressor.extension() {
+            "br" => brotli::BrotliDecompress(&mut &compressed[..], &mut decompressed).unwrap(),
+            "gz" => {
+                flate2::read::GzDecoder::new(&compressed[..])
+                    .read_to_end(&mut decompressed)
+                    .unwrap();
+            }
+            _ => unreachable!(),
+        }
+        decompressed
+    };
+
+    for format in ["br", "br:5:18", "gz", "gz:1"] {
+        assert_eq!(decompressed(&*parse(format).unwrap()), src, "{format}");
+    }
+    assert!(parse("br:12").is_err());
+    assert_eq!(
+        parse("br:9").unwrap().as_brotli(),
+        Some("br:9".parse().unwrap())
+    );
+    assert_eq!(parse("gz").unwrap().as_brotli(), None);
+    assert!(parse("lz4").is_err());
+}
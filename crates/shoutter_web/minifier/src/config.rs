@@ -0,0 +1,138 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+
+use crate::compress::{self, Brotli, Compressor};
+use crate::opt_js::JsOptions;
+use crate::pass::Pipeline;
+use crate::report::Budget;
+use crate::symbol::SymbolOptions;
+use crate::sys::env;
+
+// paths are relative to the directory node is running in (`pkg`).
+#[derive(Debug)]
+pub struct Config {
+    /// `MINIFIER_REPORT`: where to write the report. `.json` or `.csv`.
+    pub report: Option<PathBuf>,
+    /// `MINIFIER_BASELINE`: json report of a previous run to compare against.
+    pub baseline: Option<PathBuf>,
+    /// `MINIFIER_BUDGET`: see [`Budget`].
+    pub budget: Budget,
+    /// `MINIFIER_BROTLI`: brotli for the reported sizes and budgets, e.g. `br:9`.
+    /// See [`Brotli`].
+    pub brotli: Brotli,
+    /// `MINIFIER_PRECOMPRESS`: comma separated compressions to write next to each output,
+    /// e.g. `br,gz:6` for `<file>.br` and `<file>.gz`. See [`compress::parse`].
+    pub precompress: Vec<Box<dyn Compressor>>,
+    /// `MINIFIER_SOURCE_MAP`: emit `<file>.map` next to each minified javascript, by
+    /// `optimize-js` and `terser`, whichever runs first.
+    pub source_map: bool,
+    /// `MINIFIER_CACHE_DIR`: where outputs are cached between runs.
+    /// `MINIFIER_NO_CACHE` disables the cache.
+    pub cache_dir: Option<PathBuf>,
+    /// `MINIFIER_STRIP_SECTIONS`: comma separated wasm custom sections to strip, e.g. `*`.
+    /// `MINIFIER_KEEP_SECTIONS`: comma separated sections to keep all the same, e.g. `name`.
+    /// `MINIFIER_RENAME_NAMES`: rename exported functions in the kept `name` section.
+    /// `MINIFIER_PIN_EXPORTS`: comma separated wasm exports not to be renamed, e.g. `memory`.
+    /// `MINIFIER_USED_EXPORTS`: comma separated wasm exports used by callers of `init`,
+    /// e.g. `memory`. drops the other exports unused by the glue.
+    /// `MINIFIER_KEEP_UNUSED`: keep wasm exports and imports unused by the glue.
+    /// `MINIFIER_OPTIMIZE_WASM`: optimize wasm function bodies as well.
+    /// `MINIFIER_COMPACT_DATA`: merge wasm data segments and drop their zeros.
+    /// `MINIFIER_DEDUP_DATA`: deduplicate strings in wasm data as well. may rewrite
+    /// constants that only happen to equal the address of a duplicate.
+    /// `MINIFIER_STRIP_PANICS`: replace panic messages and locations in wasm data with
+    /// placeholders, writing `<file>.panics.json` to look them up.
+    pub symbol: SymbolOptions,
+    /// `MINIFIER_JS_SKIP`: comma separated passes not to run on wasm-bindgen glue,
+    /// e.g. `inline-functions,text-codec`. See [`JsOptions`].
+    pub js: JsOptions,
+    /// `MINIFIER_SKIP_PASSES`: comma separated passes not to run, e.g. `terser,clean-css`.
+    /// `MINIFIER_JS`: `terser` (default) or `swc`.
+    /// `MINIFIER_HTML`: `html-minifier` (default) or `optimize-html`.
+    /// `MINIFIER_CSS`: `clean-css` (default) or `lightningcss`.
+    /// `MINIFIER_MANGLE_CLASSES`: shorten class names across css and html, and wasm data for
+    /// `MINIFIER_WASM_CLASSES`.
+    /// See [`Pipeline`].
+    pub passes: Pipeline,
+    /// `MINIFIER_KEEP_CLASSES`: comma separated globs of classes not to be shortened,
+    /// e.g. `btn-*` for classes put together at runtime.
+    pub keep_classes: Vec<String>,
+    /// `MINIFIER_WASM_CLASSES`: comma separated globs of classes to be shortened in wasm
+    /// data as well. Others found there are kept, as the same text may mean something else.
+    pub wasm_classes: Vec<String>,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Config> {
+        Ok(Config {
+            report: env::var("MINIFIER_REPORT").map(PathBuf::from),
+            baseline: env::var("MINIFIER_BASELINE").map(PathBuf::from),
+            budget: parsed("MINIFIER_BUDGET")?,
+            brotli: parsed("MINIFIER_BROTLI")?,
+            precompress: list("MINIFIER_PRECOMPRESS")
+                .iter()
+                .map(|x| compress::parse(x))
+                .collect::<Result<_>>()
+                .context("failed to parse MINIFIER_PRECOMPRESS")?,
+            source_map: flag("MINIFIER_SOURCE_MAP"),
+            cache_dir: (!flag("MINIFIER_NO_CACHE")).then(|| {
+                env::var("MINIFIER_CACHE_DIR")
+                    .map_or_else(|| PathBuf::from("../../.minifier-cache"), PathBuf::from)
+            }),
+            symbol: SymbolOptions {
+                strip_sections: list("MINIFIER_STRIP_SECTIONS"),
+                keep_sections: list("MINIFIER_KEEP_SECTIONS"),
+                rename_names: flag("MINIFIER_RENAME_NAMES"),
+                pinned_exports: list("MINIFIER_PIN_EXPORTS"),
+                used_exports: list("MINIFIER_USED_EXPORTS"),
+                keep_unused: flag("MINIFIER_KEEP_UNUSED"),
+                optimize_code: flag("MINIFIER_OPTIMIZE_WASM"),
+                compact_data: flag("MINIFIER_COMPACT_DATA"),
+                dedup_data: flag("MINIFIER_DEDUP_DATA"),
+                strip_panics: flag("MINIFIER_STRIP_PANICS"),
+            },
+            js: JsOptions::skipping(&list("MINIFIER_JS_SKIP"))
+                .context("failed to parse MINIFIER_JS_SKIP")?,
+            passes: Pipeline::new(
+                parsed("MINIFIER_JS")?,
+                parsed("MINIFIER_HTML")?,
+                parsed("MINIFIER_CSS")?,
+                flag("MINIFIER_MANGLE_CLASSES"),
+                &list("MINIFIER_SKIP_PASSES"),
+            )
+            .context("failed to parse MINIFIER_SKIP_PASSES")?,
+            keep_classes: list("MINIFIER_KEEP_CLASSES"),
+            wasm_classes: list("MINIFIER_WASM_CLASSES"),
+        })
+    }
+}
+
+// the default when unset.
+fn parsed<T>(key: &str) -> Result<T>
+where
+    T: FromStr<Err = anyhow::Error> + Default,
+{
+    Ok(env::var(key)
+        .map(|x| x.parse())
+        .transpose()
+        .with_context(|| format!("failed to parse {key}"))?
+        .unwrap_or_default())
+}
+
+fn flag(key: &str) -> bool {
+    matches!(env::var(key).as_deref(), Some("1" | "true"))
+}
+
+fn list(key: &str) -> Vec<String> {
+    env::var(key)
+        .map(|x| {
+            x.split(',')
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+                .map(str::to_owned)
+                .collect()
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,99 @@
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Phase {
+    Read,
+    Classes,
+    Symbol,
+    OptimizeJs,
+    Terser,
+    Html,
+    Css,
+    Write,
+}
+
+impl Display for Phase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Phase::Read => "read",
+            Phase::Classes => "class mangling",
+            Phase::Symbol => "symbol minification",
+            Phase::OptimizeJs => "javascript optimization",
+            Phase::Terser => "terser",
+            Phase::Html => "html minification",
+            Phase::Css => "css minification",
+            Phase::Write => "write",
+        })
+    }
+}
+
+/// 1-based line and 0-based column, as swc and terser report them.
+#[derive(Debug, Clone, Copy)]
+pub struct SourceLocation {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// error pointing at a position of the source being processed.
+#[derive(Debug)]
+pub struct LocatedError {
+    pub location: SourceLocation,
+    pub message: String,
+}
+
+impl Display for LocatedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+impl std::error::Error for LocatedError {}
+
+/// failure of a single file. other files keep being processed.
+#[derive(Debug)]
+pub struct MinifyError {
+    pub path: PathBuf,
+    pub phase: Phase,
+    pub location: Option<SourceLocation>,
+    pub source: anyhow::Error,
+}
+
+impl MinifyError {
+    pub fn new(path: &Path, phase: Phase, source: anyhow::Error) -> Self {
+        let location = source
+            .chain()
+            .find_map(|e| e.downcast_ref::<LocatedError>())
+            .map(|e| e.location);
+        Self {
+            path: path.to_owned(),
+            phase,
+            location,
+            source,
+        }
+    }
+}
+
+impl Display for MinifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some(SourceLocation { line, column }) = self.location {
+            write!(f, ":{line}:{column}")?;
+        }
+        write!(f, ": {} failed: {:#}", self.phase, self.source)
+    }
+}
+impl std::error::Error for MinifyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+pub trait ResultExt<T> {
+    fn phase(self, path: &Path, phase: Phase) -> Result<T, MinifyError>;
+}
+
+impl<T> ResultExt<T> for anyhow::Result<T> {
+    fn phase(self, path: &Path, phase: Phase) -> Result<T, MinifyError> {
+        self.map_err(|e| MinifyError::new(path, phase, e))
+    }
+}
@@ -2,15 +2,29 @@
 #![feature(let_chains)]
 #![feature(box_patterns)]
 
+mod cache;
+mod compress;
+mod config;
+mod error;
+mod opt_class;
+mod opt_css;
+mod opt_data;
+mod opt_html;
 mod opt_js;
+mod opt_panic;
+mod opt_wasm;
+mod pass;
+mod report;
 mod symbol;
 mod sys;
+mod verify;
 
-use std::future::Future;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
-use std::pin::Pin;
+use std::rc::Rc;
 
-use anyhow::Result;
+use anyhow::{ensure, Result};
+use futures::future::join_all;
 use once_cell::sync::Lazy;
 use tracing::{Metadata, Subscriber};
 use tracing_subscriber::fmt::format::{FmtSpan, Pretty};
@@ -22,28 +36,37 @@ use wasm_bindgen::prelude::wasm_bindgen;
 use wasm_bindgen::JsValue;
 use web_sys::console;
 
-use crate::sys::{brotli, fs};
+use crate::cache::Cache;
+use crate::compress::Compressor;
+use crate::config::Config;
+use crate::error::{MinifyError, Phase, ResultExt};
+use crate::opt_class::ClassMap;
+use crate::pass::Shared;
+use crate::report::{FileReport, PassStats, ProcessStats, Report, SectionSaving};
+use crate::sys::fs;
 
 #[wasm_bindgen(start)]
 async fn main() {
     std::panic::set_hook(Box::new(console_error_panic_hook::hook));
-    start().await.unwrap();
+    if let Err(e) = start().await {
+        println(format!("error: {e:#}"));
+        sys::process::set_exit_code(1);
+    }
 }
 
 static ORIGINAL_DIR: Lazy<&Path> = Lazy::new(|| Path::new("../../dist"));
 static MINIFIED_DIR: Lazy<&Path> = Lazy::new(|| Path::new("../../dist-minified"));
 
-struct ProcessStats {
-    origin_size: usize,
-    minified_size: Option<usize>,
-    brotlied_size: usize,
-}
-
 // track file size among minify processes.
-struct TrackedFile {
+pub struct TrackedFile {
     content: Vec<u8>,
     path: PathBuf,
     original_len: usize,
+    source_map: Option<sourcemap::SourceMap>,
+    sections: Vec<SectionSaving>,
+    passes: Vec<PassStats>,
+    // placeholder => original text of panic messages stripped from wasm
+    panics: BTreeMap<String, String>,
 }
 
 impl TrackedFile {
@@ -55,27 +78,47 @@ impl TrackedFile {
             content,
             path,
             original_len,
+            source_map: None,
+            sections: vec![],
+            passes: vec![],
+            panics: BTreeMap::new(),
         })
     }
 
-    async fn minify_str<F>(&mut self, minifier: F) -> Result<()>
-    where
-        F: FnOnce(String) -> Pin<Box<dyn Future<Output = Result<String>>>>,
-    {
-        let input = String::from_utf8(self.content.clone())?;
-        let updated = minifier(input).await?;
-        self.content = updated.into_bytes();
-        Ok(())
+    fn file_name(&self) -> String {
+        self.path.file_name().unwrap().to_str().unwrap().to_owned()
     }
 
-    async fn finish(self) -> Result<ProcessStats> {
+    async fn finish(self, config: &Config) -> Result<ProcessStats> {
         let maybe_minified_size = self.content.len();
-        let brotlied_size = brotli::compress(&self.content).len();
-        fs::write_file(
-            &MINIFIED_DIR.join(self.path.file_name().unwrap()),
-            &self.content,
-        )
-        .await?;
+        // brotli at quality 11 takes seconds per MiB under node, so it runs once when
+        // `.br` files use the same settings as the reported size.
+        let mut brotlied_size = None;
+        let file_name = self.file_name();
+        fs::write_file(&MINIFIED_DIR.join(&file_name), &self.content).await?;
+        for compressor in &config.precompress {
+            let compressed = compressor.compress(&self.content)?;
+            if compressor.as_brotli() == Some(config.brotli) {
+                brotlied_size = Some(compressed.len());
+            }
+            let path = MINIFIED_DIR.join(format!("{file_name}.{}", compressor.extension()));
+            fs::write_file(&path, &compressed).await?;
+        }
+        let brotlied_size = match brotlied_size {
+            Some(size) => size,
+            None => config.brotli.compress(&self.content)?.len(),
+        };
+        // not referenced by `sourceMappingURL`; kept aside for symbolicating stack traces.
+        if let Some(source_map) = self.source_map {
+            let mut buf = vec![];
+            source_map.to_writer(&mut buf)?;
+            fs::write_file(&MINIFIED_DIR.join(file_name.clone() + ".map"), &buf).await?;
+        }
+        // kept aside as well, for looking up placeholders in panics.
+        if !self.panics.is_empty() {
+            let buf = serde_json::to_vec_pretty(&self.panics)?;
+            fs::write_file(&MINIFIED_DIR.join(file_name + ".panics.json"), &buf).await?;
+        }
         Ok(ProcessStats {
             origin_size: self.original_len,
             minified_size: (self.original_len != maybe_minified_size)
@@ -85,13 +128,6 @@ impl TrackedFile {
     }
 }
 
-// Async Closure
-macro_rules! ac {
-    (|$i:ident$(:$ty:ty)?| $b:block) => {
-        |$i$(:$ty)?| { Box::pin(async move { $b }) as Pin<Box<dyn Future<Output = _>>> }
-    };
-}
-
 async fn start() -> Result<()> {
     let fmt_layer = tracing_subscriber::fmt::layer()
         .with_ansi(true)
@@ -106,28 +142,96 @@ async fn start() -> Result<()> {
         .with(perf_layer)
         .init();
 
+    let config = Config::from_env()?;
+
     fs::rimraf(*MINIFIED_DIR).await?;
     fs::mkdir(*MINIFIED_DIR).await?;
 
     let mut file_paths = fs::read_dir(*ORIGINAL_DIR).await?;
+    file_paths.retain(|path| {
+        matches!(
+            path.extension().and_then(|x| x.to_str()),
+            Some("html" | "css" | "js" | "wasm")
+        )
+    });
+    // readdir order is not guaranteed. keep the report stable across runs.
+    file_paths.sort();
 
     enum ProcessTarget {
         Individual(TrackedFile),
         WasmBindgen { js: TrackedFile, wasm: TrackedFile },
     }
 
+    impl ProcessTarget {
+        fn files(&self) -> Vec<&TrackedFile> {
+            match self {
+                ProcessTarget::Individual(i) => vec![i],
+                ProcessTarget::WasmBindgen { js, wasm } => vec![js, wasm],
+            }
+        }
+
+        fn into_files(self) -> Vec<TrackedFile> {
+            match self {
+                ProcessTarget::Individual(i) => vec![i],
+                ProcessTarget::WasmBindgen { js, wasm } => vec![js, wasm],
+            }
+        }
+    }
+
     let mut js = vec![];
     let mut wasm = vec![];
     let mut targets = vec![];
+    let mut errors = vec![];
+
+    let read = join_all(
+        file_paths
+            .iter()
+            .map(|path| async move { TrackedFile::new(path).await.phase(path, Phase::Read) }),
+    )
+    .await;
+
+    // classes are renamed the same way in every file, so they are collected up front.
+    let classes = if config.passes.has("mangle-classes") {
+        let files = read.iter().flatten().collect::<Vec<_>>();
+        let of = |extension: &str| {
+            files
+                .iter()
+                .filter(|f| f.path.extension().is_some_and(|x| x == extension))
+                .map(|f| &f.content[..])
+                .collect::<Vec<_>>()
+        };
+        let html = of("html")
+            .into_iter()
+            .filter_map(|x| std::str::from_utf8(x).ok())
+            .collect::<Vec<_>>();
+        let classes = ClassMap::new(
+            &of("css"),
+            &html,
+            &of("wasm"),
+            &config.keep_classes,
+            &config.wasm_classes,
+        )
+        .map_err(|e| e.context("failed to collect classes"))?;
+        Some(Rc::new(classes))
+    } else {
+        None
+    };
+    // outputs of a target depend on the other files through the renamed classes.
+    let classes_key = classes.as_ref().map(|x| x.to_string());
 
     // grouping
     // html, css => Individual
     // js if wasm pair found => WasmBindgen { js, wasm }
     // other js and wasm => Individual
-    while let Some(file) = file_paths.pop() {
-        let Some(ext @ ("html" | "css" | "js" | "wasm")) = file.extension().and_then(|x| x.to_str()) else { continue };
-        let file = TrackedFile::new(&file).await?;
-        match ext {
+    for file in read {
+        let file = match file {
+            Ok(file) => file,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            }
+        };
+        match file.path.extension().unwrap().to_str().unwrap() {
             "html" | "css" => targets.push(ProcessTarget::Individual(file)),
             "wasm" => wasm.push(file),
             "js" => js.push(file),
@@ -153,69 +257,134 @@ async fn start() -> Result<()> {
     }
 
     // minify
-    let minify_html = ac!(|x: String| { sys::minifier::html(&x).await });
-    let minify_css = ac!(|x: String| { sys::minifier::css(&x).await });
-    let minify_js = ac!(|x: String| { sys::minifier::js(&opt_js::optimize_js(x)).await });
-    for target in &mut targets {
-        match target {
-            ProcessTarget::Individual(i) => match i.path.extension().unwrap().to_str().unwrap() {
-                "html" => i.minify_str(&minify_html).await?,
-                "css" => i.minify_str(&minify_css).await?,
-                "js" => i.minify_str(&minify_js).await?,
-                _ => {}
-            },
-            ProcessTarget::WasmBindgen { js, wasm } => {
-                symbol::minify_symbol(&mut wasm.content, &mut js.content).await;
-                js.minify_str(&minify_js).await?;
+    // targets are independent of each other so run them concurrently.
+    // a failing target is reported at the end and does not stop the others.
+    let config = &config;
+    let (classes, classes_key) = (&classes, &classes_key);
+    let cache = match &config.cache_dir {
+        Some(dir) => match Cache::new(dir.clone(), config).await {
+            Ok(cache) => Some(cache),
+            Err(e) => {
+                tracing::warn!("cache disabled: {e:#}");
+                None
             }
+        },
+        None => None,
+    };
+    let cache = &cache;
+    let processed = join_all(targets.into_iter().map(|mut target| async move {
+        let key = cache.as_ref().map(|cache| {
+            let mut inputs = target
+                .files()
+                .into_iter()
+                .map(|f| (f.file_name(), &f.content[..]))
+                .collect::<Vec<_>>();
+            if let Some(classes) = classes_key {
+                inputs.push(("classes".to_owned(), classes.as_bytes()));
+            }
+            cache.key(&inputs)
+        });
+        if let (Some(cache), Some(key)) = (cache, &key)
+            && let Some(files) = cache.restore(key, *MINIFIED_DIR).await
+        {
+            return Ok(files);
         }
-    }
 
-    // finalize and show result
-    let mut files = vec![];
-    for target in targets {
-        match target {
-            ProcessTarget::Individual(i) => files.push(i),
+        match &mut target {
+            ProcessTarget::Individual(i) => {
+                let mut shared = Shared {
+                    classes: classes.clone(),
+                    ..Default::default()
+                };
+                config.passes.run(i, &mut shared, config).await?
+            }
+            // wasm first, so that the glue can follow its renamed symbols.
             ProcessTarget::WasmBindgen { js, wasm } => {
-                files.push(js);
-                files.push(wasm);
+                let usage = std::str::from_utf8(&js.content)
+                    .map_err(anyhow::Error::from)
+                    .and_then(opt_js::count_wasm_symbols)
+                    .phase(&js.path, Phase::OptimizeJs)?;
+                let mut shared = Shared {
+                    classes: classes.clone(),
+                    usage: Some(usage),
+                    symbols: None,
+                };
+                config.passes.run(wasm, &mut shared, config).await?;
+                config.passes.run(js, &mut shared, config).await?;
             }
         }
+
+        // finalize
+        let mut entry = cache::Entry {
+            outputs: vec![],
+            files: vec![],
+        };
+        for f in target.into_files() {
+            let file_name = f.file_name();
+            if f.source_map.is_some() {
+                entry.outputs.push(format!("{file_name}.map"));
+            }
+            if !f.panics.is_empty() {
+                entry.outputs.push(format!("{file_name}.panics.json"));
+            }
+            for compressor in &config.precompress {
+                entry
+                    .outputs
+                    .push(format!("{file_name}.{}", compressor.extension()));
+            }
+            entry.outputs.push(file_name.clone());
+            let path = f.path.clone();
+            let sections = f.sections.clone();
+            let passes = f.passes.clone();
+            let stats = f.finish(config).await.phase(&path, Phase::Write)?;
+            entry.files.push(FileReport {
+                file_name,
+                stats,
+                sections,
+                passes,
+            });
+        }
+        if let (Some(cache), Some(key)) = (cache, &key)
+            && let Err(e) = cache.store(key, *MINIFIED_DIR, &entry).await
+        {
+            tracing::warn!("failed to store outputs to the cache: {e:#}");
+        }
+        Ok::<_, MinifyError>(entry.files)
+    }))
+    .await;
+
+    // show result
+    let mut report = Report::default();
+    for processed in processed {
+        match processed {
+            Ok(files) => report.files.extend(files),
+            Err(e) => errors.push(e),
+        }
     }
 
-    let mut file_name_max_len = 0;
-    for f in &files {
-        file_name_max_len = file_name_max_len.max(
-            f.path
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .chars()
-                .count(),
-        );
+    let baseline = match &config.baseline {
+        Some(path) => Some(Report::read(path).await?),
+        None => None,
+    };
+    report.print(baseline.as_ref());
+
+    if let Some(path) = &config.report {
+        report.write(path).await?;
     }
 
-    println(format!(
-        "{1:>0$}: {2:>10} {3:>10} {4:>10}",
-        file_name_max_len, "filename", "origin", "minify", "brotli",
-    ));
-
-    for f in files {
-        let file_name = f.path.file_name().unwrap().to_str().unwrap().to_owned();
-        let stats = f.finish().await?;
-        let kib = |n| format!("{:7.02}KiB", (n as f64) / 1024.0);
-        println(format!(
-            "{1:>0$}: {2:>} {3:>} {4:>}",
-            file_name_max_len,
-            file_name,
-            kib(stats.origin_size),
-            stats
-                .minified_size
-                .map_or_else(|| format!("{:>10}", "---KiB"), kib),
-            kib(stats.brotlied_size),
-        ))
+    let violations = config.budget.check(&report);
+    for violation in &violations {
+        println(format!("budget exceeded: {violation}"));
+    }
+    for error in &errors {
+        println(format!("error: {error}"));
     }
+    ensure!(
+        errors.is_empty() && violations.is_empty(),
+        "{} file(s) failed, {} size budget(s) exceeded",
+        errors.len(),
+        violations.len(),
+    );
 
     Ok(())
 }
@@ -0,0 +1,440 @@
+// shortens class names the same way across css, html and strings in wasm data.
+//
+// classes come from selectors in css files. a class found in wasm data is renamed only
+// when the caller opts into it, as the same text may be a field name or a message, and
+// only where every occurrence is a whole `&str` whose length code passes right after
+// its pointer: the name is written over the start and the length operands are
+// rewritten. classes put together at runtime, e.g. `format!("btn-{kind}")`, can't be
+// seen and must be kept by the caller. stylist's are shortened by the app, which gives
+// its style manager the prefix `s` in place of `stylist`.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+
+use anyhow::Result;
+use wasmparser::Operator;
+
+use crate::opt_data::{self, memarg, overlaps, read};
+use crate::opt_html;
+use crate::report::glob_match;
+
+/// class => shorter class
+#[derive(Debug, Default)]
+pub struct ClassMap {
+    renames: BTreeMap<String, String>,
+}
+
+fn is_ident(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b >= 0x80
+}
+
+// `.class` in css, without the dot. escaped names are left out.
+fn css_classes(css: &[u8]) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut i = 0;
+    while i < css.len() {
+        // `1.5em`, `a.png`
+        if css[i] != b'.' || i > 0 && (is_ident(css[i - 1]) || css[i - 1] == b'\\') {
+            i += 1;
+            continue;
+        }
+        let start = i + 1;
+        let end = start + css[start..].iter().take_while(|&&b| is_ident(b)).count();
+        let is_name = css
+            .get(start)
+            .is_some_and(|&b| b.is_ascii_alphabetic() || b == b'-' || b == b'_');
+        if is_name && css.get(end) != Some(&b'\\') {
+            ranges.push(start..end);
+        }
+        i = end;
+    }
+    ranges
+}
+
+// maximal runs of identifier bytes.
+fn words(data: &[u8]) -> Vec<Range<usize>> {
+    let mut words = vec![];
+    let mut start = None;
+    for (i, &b) in data.iter().enumerate() {
+        match (is_ident(b), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                words.push(s..i);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        words.push(s..data.len());
+    }
+    words
+}
+
+fn data_ranges(wasm: &[u8]) -> Result<Vec<Range<usize>>> {
+    let mut ranges = vec![];
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        if let wasmparser::Payload::DataSection(section) = payload? {
+            for data in section {
+                let data = data?;
+                let start = data.data.as_ptr() as usize - wasm.as_ptr() as usize;
+                ranges.push(start..start + data.data.len());
+            }
+        }
+    }
+    Ok(ranges)
+}
+
+// a `&str` in wasm data whose length only comes from code.
+struct Literal {
+    /// range of the text in the module
+    text: Range<usize>,
+    /// ranges of the `i32.const` operands passing its length in the module
+    lens: Vec<Range<usize>>,
+}
+
+// texts in active segments referred by `i32.const ptr; i32.const len` and nothing else:
+// no other length, no memory offset or global, no pointer in data and no other
+// `&str` overlapping.
+fn literals(wasm: &[u8]) -> Result<Vec<Literal>> {
+    // address => data, and address => offset in the module, of active segments
+    let mut segments = BTreeMap::new();
+    let mut offsets = BTreeMap::new();
+    let mut fixed = HashSet::new();
+    // `i32.const` operands => the `i32.const` following each of them, if any, along with
+    // the range of its operand
+    let mut consts = HashMap::<u32, Vec<Option<(u32, Range<usize>)>>>::new();
+
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        match payload? {
+            wasmparser::Payload::GlobalSection(section) => {
+                for global in section {
+                    for op in global?.init_expr.get_operators_reader() {
+                        if let Operator::I32Const { value } = op? {
+                            fixed.insert(value as u32);
+                        }
+                    }
+                }
+            }
+            wasmparser::Payload::CodeSectionEntry(body) => {
+                let ops = body
+                    .get_operators_reader()?
+                    .into_iter_with_offsets()
+                    .collect::<wasmparser::Result<Vec<_>>>()?;
+                for (i, (op, _)) in ops.iter().enumerate() {
+                    if let Some(memarg) = memarg(op) {
+                        fixed.insert(memarg.offset as u32);
+                    }
+                    let Operator::I32Const { value } = op else { continue };
+                    // bodies end with `end`, so an operator follows every `i32.const`
+                    let next = match (ops.get(i + 1), ops.get(i + 2)) {
+                        (Some((Operator::I32Const { value: len }, start)), Some((_, end))) => {
+                            Some((*len as u32, start + 1..*end))
+                        }
+                        _ => None,
+                    };
+                    consts.entry(*value as u32).or_default().push(next);
+                }
+            }
+            wasmparser::Payload::DataSection(section) => {
+                for data in section {
+                    let data = data?;
+                    let wasmparser::DataKind::Active {
+                        memory_index: 0,
+                        offset_expr,
+                    } = data.kind
+                    else {
+                        continue;
+                    };
+                    let mut ops = offset_expr.get_operators_reader();
+                    if let Operator::I32Const { value } = ops.read()?
+                        && let Operator::End = ops.read()?
+                    {
+                        let offset = data.data.as_ptr() as usize - wasm.as_ptr() as usize;
+                        segments.insert(value as u32, data.data.to_vec());
+                        offsets.insert(value as u32, offset);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let pointers = opt_data::words(&segments);
+    let mut pairs = consts
+        .iter()
+        .flat_map(|(ptr, nexts)| nexts.iter().flatten().map(|(len, _)| (*ptr, *len)))
+        .filter(|(ptr, len)| read(&segments, *ptr, *len).is_some())
+        .collect::<Vec<_>>();
+    pairs.sort();
+    pairs.dedup();
+
+    let mut literals = vec![];
+    for (ptr, nexts) in &consts {
+        let Some(Some((len, _))) = nexts.first() else { continue };
+        let lens = nexts
+            .iter()
+            .map(|x| x.as_ref().filter(|(x, _)| x == len).map(|(_, x)| x.clone()))
+            .collect::<Option<Vec<_>>>();
+        let Some(lens) = lens else { continue };
+        if *len == 0
+            || fixed.contains(ptr)
+            || pointers.contains_key(ptr)
+            || read(&segments, *ptr, *len).is_none()
+            || overlaps(&pairs, *ptr, *len)
+        {
+            continue;
+        }
+        let (address, offset) = offsets.range(..=*ptr).next_back().unwrap();
+        let start = offset + (ptr - address) as usize;
+        literals.push(Literal {
+            text: start..start + *len as usize,
+            lens,
+        });
+    }
+    Ok(literals)
+}
+
+// signed leb128 of `value` filling `out`, padded with continuation bytes as wasm allows.
+fn write_sleb(out: &mut [u8], mut value: i32) {
+    let last = out.len() - 1;
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = (value & 0x7f) as u8 | if i < last { 0x80 } else { 0 };
+        value >>= 7;
+    }
+}
+
+// `a`, ..., `z`, `aa`, `ba`, ... lowercase only, as quirks mode matches classes
+// regardless of case.
+fn short_name(mut i: usize) -> String {
+    const FIRST: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
+    const REST: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789_-";
+    let mut name = vec![FIRST[i % FIRST.len()]];
+    i /= FIRST.len();
+    while i > 0 {
+        i -= 1;
+        name.push(REST[i % REST.len()]);
+        i /= REST.len();
+    }
+    String::from_utf8(name).unwrap()
+}
+
+impl ClassMap {
+    /// `css`, `html` and `wasm` are whole files. classes matching a glob in `keep` stay,
+    /// and those found in wasm data stay unless they match a glob in `in_wasm`.
+    pub fn new(
+        css: &[&[u8]],
+        html: &[&str],
+        wasm: &[&[u8]],
+        keep: &[String],
+        in_wasm: &[String],
+    ) -> Result<Self> {
+        let mut counts = HashMap::<&[u8], usize>::new();
+        for css in css {
+            for range in css_classes(css) {
+                *counts.entry(&css[range]).or_default() += 1;
+            }
+        }
+
+        let mut data = vec![];
+        // text => number of literals with it
+        let mut texts = HashMap::<&[u8], usize>::new();
+        for wasm in wasm {
+            for range in data_ranges(wasm)? {
+                data.push(&wasm[range]);
+            }
+            for literal in literals(wasm)? {
+                *texts.entry(&wasm[literal.text]).or_default() += 1;
+            }
+        }
+        let mut data_words = HashSet::<&[u8]>::new();
+        for data in &data {
+            for range in words(data) {
+                data_words.insert(&data[range]);
+            }
+        }
+
+        let mut html_classes = HashSet::new();
+        for html in html {
+            for class in opt_html::classes(html)? {
+                if let Some(count) = counts.get_mut(class.as_bytes()) {
+                    *count += 1;
+                }
+                html_classes.insert(class);
+            }
+        }
+
+        let mut candidates = counts
+            .iter()
+            .filter_map(|(&class, &count)| {
+                let name = std::str::from_utf8(class).ok()?;
+                if keep.iter().any(|x| glob_match(x, name)) {
+                    return None;
+                }
+                let found = data
+                    .iter()
+                    .map(|x| x.windows(class.len()).filter(|&w| w == class).count())
+                    .sum::<usize>();
+                let renamable = in_wasm.iter().any(|x| glob_match(x, name))
+                    && texts.get(class).copied().unwrap_or(0) == found;
+                (found == 0 || renamable).then_some((name, count + found))
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+        // a new name must not be a class or a word in data already.
+        let taken = |name: &str| {
+            counts.contains_key(name.as_bytes())
+                || data_words.contains(name.as_bytes())
+                || html_classes.contains(name)
+        };
+        let mut renames = BTreeMap::new();
+        let mut next = 0;
+        for (class, _) in candidates {
+            let name = loop {
+                let name = short_name(next);
+                next += 1;
+                if !taken(&name) {
+                    break name;
+                }
+            };
+            // names only get longer from here.
+            if name.len() >= class.len() {
+                break;
+            }
+            renames.insert(class.to_owned(), name);
+        }
+        Ok(Self { renames })
+    }
+
+    pub fn get(&self, class: &str) -> Option<&str> {
+        self.renames.get(class).map(String::as_str)
+    }
+
+    pub fn rename_css(&self, css: &str) -> String {
+        let mut renamed = String::with_capacity(css.len());
+        let mut last = 0;
+        for range in css_classes(css.as_bytes()) {
+            if let Some(name) = self.get(&css[range.clone()]) {
+                renamed.push_str(&css[last..range.start]);
+                renamed.push_str(name);
+                last = range.end;
+            }
+        }
+        renamed.push_str(&css[last..]);
+        renamed
+    }
+
+    pub fn rename_html(&self, html: &str) -> Result<String> {
+        opt_html::rename_classes(html, self)
+    }
+
+    /// Rewrites literals in data along with their lengths in code, in place. The rest of
+    /// an old name stays, no longer referenced.
+    pub fn rename_wasm(&self, wasm: &mut [u8]) -> Result<()> {
+        for literal in literals(wasm)? {
+            let name = std::str::from_utf8(&wasm[literal.text.clone()])
+                .ok()
+                .and_then(|x| self.get(x));
+            let Some(name) = name else { continue };
+            let start = literal.text.start;
+            wasm[start..start + name.len()].copy_from_slice(name.as_bytes());
+            for len in literal.lens {
+                write_sleb(&mut wasm[len], name.len() as i32);
+            }
+        }
+        Ok(())
+    }
+}
+
+// `<class> <renamed>` per line. goes into cache keys, as outputs depend on other files.
+impl Display for ClassMap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (class, name) in &self.renames {
+            writeln!(f, "{class} {name}")?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn rename_classes() {
+    use wasm_encoder::{
+        CodeSection, ConstExpr, DataSection, Function, FunctionSection, Instruction, MemorySection,
+        MemoryType, Module, TypeSection,
+    };
+
+    // `card` is opted into, `title` isn't as it is a field name as well, and `btn` is a
+    // part of a longer string.
+    let data = b"card\0\0\0\0title\0\0\0btn-primary";
+    let mut module = Module::new();
+    let mut types = TypeSection::new();
+    types.function([], []);
+    module.section(&types);
+    let mut functions = FunctionSection::new();
+    functions.function(0);
+    module.section(&functions);
+    let mut memories = MemorySection::new();
+    memories.memory(MemoryType {
+        minimum: 1,
+        maximum: None,
+        memory64: false,
+        shared: false,
+    });
+    module.section(&memories);
+    let mut function = Function::new([]);
+    for (ptr, len) in [(1024, 4), (1032, 5), (1040, 11), (1024, 4)] {
+        function.instruction(&Instruction::I32Const(ptr));
+        function.instruction(&Instruction::I32Const(len));
+        function.instruction(&Instruction::Drop);
+        function.instruction(&Instruction::Drop);
+    }
+    function.instruction(&Instruction::End);
+    let mut code = CodeSection::new();
+    code.function(&function);
+    module.section(&code);
+    let mut segments = DataSection::new();
+    segments.active(0, &ConstExpr::i32_const(1024), data.iter().copied());
+    module.section(&segments);
+    let mut wasm = module.finish();
+
+    let css = ".card{color:red}.title{margin:0}.btn:hover{color:blue}.layout .card{}\
+               .keep-me{background:url(a.png)}";
+    let html = r#"<div class="layout keep-me x"></div><style>.title{}</style>"#;
+    let keep = ["keep-*".to_owned()];
+    let in_wasm = ["card".to_owned(), "btn".to_owned()];
+    let classes = ClassMap::new(&[css.as_bytes()], &[html], &[&wasm], &keep, &in_wasm).unwrap();
+    assert_eq!(classes.to_string(), "card a\nlayout b\n");
+
+    assert_eq!(
+        classes.rename_css(css),
+        ".a{color:red}.title{margin:0}.btn:hover{color:blue}.b .a{}.keep-me{background:url(a.png)}"
+    );
+    assert_eq!(
+        classes.rename_html(html).unwrap(),
+        r#"<div class="b keep-me x"></div><style>.title{}</style>"#
+    );
+    classes.rename_wasm(&mut wasm).unwrap();
+    let range = data_ranges(&wasm).unwrap().remove(0);
+    assert_eq!(&wasm[range], b"aard\0\0\0\0title\0\0\0btn-primary");
+    let mut consts = vec![];
+    for payload in wasmparser::Parser::new(0).parse_all(&wasm) {
+        if let wasmparser::Payload::CodeSectionEntry(body) = payload.unwrap() {
+            for op in body.get_operators_reader().unwrap() {
+                if let Operator::I32Const { value } = op.unwrap() {
+                    consts.push(value);
+                }
+            }
+        }
+    }
+    assert_eq!(consts, [1024, 1, 1032, 5, 1040, 11, 1024, 1]);
+
+    let mut len = [0; 2];
+    write_sleb(&mut len, 1);
+    assert_eq!(len, [0x81, 0x00]);
+    assert_eq!(
+        wasmparser::BinaryReader::new(&len).read_var_i32().unwrap(),
+        1
+    );
+}
@@ -0,0 +1,105 @@
+// css minification without node, in place of clean-css.
+//
+// lightningcss merges rules with the same selectors or declarations, collapses
+// longhands into shorthands and shortens colors, units and numbers.
+
+use anyhow::{anyhow, Result};
+use lightningcss::stylesheet::{
+    MinifyOptions, ParserOptions, PrinterOptions, StyleAttribute, StyleSheet,
+};
+
+use crate::error::{LocatedError, SourceLocation};
+
+pub fn minify_css(css: &str, file_name: &str) -> Result<String> {
+    let mut sheet = StyleSheet::parse(
+        css,
+        ParserOptions {
+            filename: file_name.to_owned(),
+            ..Default::default()
+        },
+    )
+    .map_err(|e| match &e.loc {
+        // lightningcss counts lines from 0 and columns from 1.
+        Some(loc) => LocatedError {
+            location: SourceLocation {
+                line: loc.line as usize + 1,
+                column: loc.column.saturating_sub(1) as usize,
+            },
+            message: e.kind.to_string(),
+        }
+        .into(),
+        None => anyhow!("{e}"),
+    })?;
+    sheet
+        .minify(MinifyOptions::default())
+        .map_err(|e| anyhow!("{e}"))?;
+    let printed = sheet
+        .to_css(PrinterOptions {
+            minify: true,
+            ..Default::default()
+        })
+        .map_err(|e| anyhow!("{e}"))?;
+    Ok(printed.code)
+}
+
+/// Declarations of a `style` attribute.
+pub fn minify_style_attribute(css: &str) -> Result<String> {
+    let mut declarations =
+        StyleAttribute::parse(css, ParserOptions::default()).map_err(|e| anyhow!("{e}"))?;
+    declarations.minify(MinifyOptions::default());
+    let printed = declarations
+        .to_css(PrinterOptions {
+            minify: true,
+            ..Default::default()
+        })
+        .map_err(|e| anyhow!("{e}"))?;
+    Ok(printed.code)
+}
+
+#[test]
+fn minify() {
+    let css = "
+        html {
+            font-family: sans-serif;
+        }
+        .a {
+            color: #ff0000;
+            padding-top: 0px;
+            padding-right: 4px;
+            padding-bottom: 0px;
+            padding-left: 4px;
+        }
+        .b {
+            color: #ff0000;
+            padding: 0 4px;
+        }
+    ";
+    assert_eq!(
+        minify_css(css, "index.css").unwrap(),
+        "html{font-family:sans-serif}.a,.b{color:red;padding:0 4px}"
+    );
+
+    let err = minify_css("html {\n  color: red;\n}\n..a {}", "index.css").unwrap_err();
+    let location = err.downcast_ref::<LocatedError>().unwrap().location;
+    assert_eq!(location.line, 4);
+}
+
+// clean-css output isn't committed. `just clean-css-corpus && cargo test -- --ignored`
+#[test]
+#[ignore = "needs `just clean-css-corpus`"]
+fn same_as_clean_css() {
+    use std::fs;
+    use std::path::Path;
+
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    for path in ["../assets/index.css", "src/corpus/stylist.css"] {
+        let css = fs::read_to_string(dir.join(path)).unwrap();
+        let minified = minify_css(&css, path).unwrap();
+        let name = Path::new(path).file_stem().unwrap().to_str().unwrap();
+        let clean_css = format!("src/corpus/{name}.clean-css.css");
+        let clean_css = fs::read_to_string(dir.join(&clean_css))
+            .unwrap_or_else(|_| panic!("no {clean_css}, run `just clean-css-corpus`"));
+        // the same rules once clean-css output goes through lightningcss as well
+        assert_eq!(minify_css(&clean_css, path).unwrap(), minified, "{path}");
+    }
+}
@@ -0,0 +1,440 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use anyhow::Result;
+use wasm_encoder::Encode;
+use wasmparser::Operator;
+
+use crate::opt_panic;
+
+// leaving out a zero run is worth it when it is longer than a segment header:
+// memory index, offset expression and length.
+const MIN_ZERO_GAP: usize = 8;
+// strings shorter than this are not worth the risk of deduplicating.
+const MIN_DEDUP_LEN: usize = 16;
+const MAX_DEDUP_LEN: usize = 4096;
+
+/// Active data segments to be emitted instead of the original ones.
+pub struct DataPlan {
+    /// (offset, bytes) sorted by offset
+    pub segments: Vec<(u32, Vec<u8>)>,
+    /// `i32.const` operands in code pointing to deduplicated data
+    pub consts: HashMap<i32, i32>,
+    /// (ptr, len) => new len of stripped texts, for `i32.const`s in a row in code
+    pub lens: HashMap<(i32, i32), i32>,
+    /// bytes saved in the data section
+    pub saved: i64,
+    /// placeholder => original text of panic messages and locations (see `opt_panic`)
+    pub panics: BTreeMap<String, String>,
+}
+
+impl DataPlan {
+    pub fn encode(&self) -> wasm_encoder::DataSection {
+        let mut section = wasm_encoder::DataSection::new();
+        for (offset, data) in &self.segments {
+            section.active(
+                0,
+                &wasm_encoder::ConstExpr::i32_const(*offset as i32),
+                data.iter().copied(),
+            );
+        }
+        section
+    }
+}
+
+/// Merges adjacent active segments and leaves out zero runs, which are zero-initialized
+/// memory anyway. With `dedup`, also points `&str`s with the same content to a single copy.
+/// With `strip_panics`, replaces panic messages and locations with placeholders.
+///
+/// Returns `None`, with a warning, when the module is out of the cases known to be safe:
+/// imported or shared memory (not zeroed), segments referred by index (passive ones,
+/// `memory.init` and `data.drop`) and overlapping segments.
+pub fn plan(wasm: &[u8], dedup: bool, strip_panics: bool) -> Result<Option<DataPlan>> {
+    let skip = |reason: &str| {
+        tracing::warn!("data segments are left as they are: {reason}");
+        Ok(None)
+    };
+    let mut segments = BTreeMap::new();
+    let mut section_size = 0;
+    let mut defined_memory = false;
+    let mut references = References::default();
+
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        match payload? {
+            wasmparser::Payload::ImportSection(section) => {
+                for import in section {
+                    if let wasmparser::TypeRef::Memory(_) = import?.ty {
+                        return skip("memory is imported");
+                    }
+                }
+            }
+            wasmparser::Payload::MemorySection(section) => {
+                for memory in section {
+                    let memory = memory?;
+                    if memory.shared || memory.memory64 {
+                        return skip("memory is shared or 64-bit");
+                    }
+                    defined_memory = true;
+                }
+            }
+            wasmparser::Payload::GlobalSection(section) => {
+                for global in section {
+                    for op in global?.init_expr.get_operators_reader() {
+                        if let Operator::I32Const { value } = op? {
+                            references.fixed.insert(value as u32);
+                        }
+                    }
+                }
+            }
+            // the count is rewritten by the caller. segments are only referred by index
+            // from `memory.init` and `data.drop`, which are checked below.
+            wasmparser::Payload::DataCountSection { .. } => {}
+            wasmparser::Payload::CodeSectionEntry(body) => {
+                // bodies end with `end`, so every run is followed by some operator
+                let mut run = vec![];
+                for op in body.get_operators_reader()? {
+                    let op = op?;
+                    if let Operator::MemoryInit { .. } | Operator::DataDrop { .. } = op {
+                        return skip("segments are referred by index");
+                    }
+                    references.add(&run, &op);
+                    match op {
+                        Operator::I32Const { value } => run.push(value as u32),
+                        _ => run.clear(),
+                    }
+                }
+            }
+            wasmparser::Payload::DataSection(section) => {
+                section_size = section.range().len();
+                for data in section {
+                    let data = data?;
+                    let wasmparser::DataKind::Active {
+                        memory_index: 0,
+                        offset_expr,
+                    } = data.kind
+                    else {
+                        return skip("passive segment");
+                    };
+                    let ops = offset_expr
+                        .get_operators_reader()
+                        .into_iter()
+                        .collect::<wasmparser::Result<Vec<_>>>()?;
+                    let [Operator::I32Const { value }, Operator::End] = ops[..] else {
+                        return skip("segment offset is not a constant");
+                    };
+                    // later segments may overwrite earlier ones
+                    if segments.insert(value as u32, data.data.to_vec()).is_some() {
+                        return skip("overlapping segments");
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    if !defined_memory || segments.is_empty() {
+        return Ok(None);
+    }
+    let mut last_end = 0;
+    for (offset, data) in &segments {
+        if (*offset as u64) < last_end {
+            return skip("overlapping segments");
+        }
+        last_end = *offset as u64 + data.len() as u64;
+    }
+
+    let (panics, lens) = if strip_panics {
+        let (panics, lens) = opt_panic::strip(&mut segments, &references);
+        if panics.is_empty() {
+            tracing::warn!("no panic message or location could be stripped");
+        }
+        (panics, lens)
+    } else {
+        Default::default()
+    };
+    let consts = if dedup {
+        deduplicate(&mut segments, &references)
+    } else {
+        HashMap::new()
+    };
+
+    let mut plan = DataPlan {
+        segments: compact(segments),
+        consts,
+        lens,
+        saved: 0,
+        panics,
+    };
+    let mut encoded = vec![];
+    plan.encode().encode(&mut encoded);
+    plan.saved = section_size as i64 - encoded.len() as i64;
+    Ok(Some(plan))
+}
+
+/// Addresses possibly used by the code.
+#[derive(Default)]
+pub struct References {
+    /// used in ways we can't follow: memory offsets and constants in globals.
+    pub fixed: HashSet<u32>,
+    /// `i32.const` operands in code => the `i32.const` following each of them, if any.
+    /// rust passes `&str` as a pointer followed by its length.
+    pub consts: HashMap<u32, Vec<Option<u32>>>,
+    /// three `i32.const`s in a row, as in `panic(msg_ptr, msg_len, &LOCATION)`.
+    pub triples: Vec<[u32; 3]>,
+}
+
+impl References {
+    /// (ptr, len) of every two `i32.const`s in a row, sorted by ptr.
+    pub fn pairs(&self) -> Vec<(u32, u32)> {
+        let mut pairs = self
+            .consts
+            .iter()
+            .flat_map(|(ptr, nexts)| nexts.iter().flatten().map(|len| (*ptr, *len)))
+            .collect::<Vec<_>>();
+        pairs.sort();
+        pairs.dedup();
+        pairs
+    }
+
+    // `run` is the `i32.const` operands right before `op`
+    fn add(&mut self, run: &[u32], op: &Operator) {
+        let next = match op {
+            Operator::I32Const { value } => Some(*value as u32),
+            _ => None,
+        };
+        if let Some(prev) = run.last() {
+            self.consts.entry(*prev).or_default().push(next);
+        }
+        if let (Some(next), [.., a, b]) = (next, run) {
+            self.triples.push([*a, *b, next]);
+        }
+        if let Some(memarg) = memarg(op) {
+            self.fixed.insert(memarg.offset as u32);
+        }
+    }
+}
+
+/// Whether any of sorted `ranges` other than (`ptr`, `len`) itself overlaps it.
+pub fn overlaps(ranges: &[(u32, u32)], ptr: u32, len: u32) -> bool {
+    let end = ptr.saturating_add(len);
+    ranges
+        .iter()
+        .take_while(|(start, _)| *start < end)
+        .filter(|x| **x != (ptr, len))
+        .any(|(start, other)| start.saturating_add(*other) > ptr)
+}
+
+/// Bytes of `len` at `ptr`, if they are within a single segment.
+pub fn read(segments: &BTreeMap<u32, Vec<u8>>, ptr: u32, len: u32) -> Option<&[u8]> {
+    let (offset, data) = segments.range(..=ptr).next_back()?;
+    let start = (ptr - offset) as usize;
+    data.get(start..start.checked_add(len as usize)?)
+}
+
+/// Mutable version of `read`.
+pub fn read_mut(segments: &mut BTreeMap<u32, Vec<u8>>, ptr: u32, len: u32) -> Option<&mut [u8]> {
+    let (offset, data) = segments.range_mut(..=ptr).next_back()?;
+    let start = (ptr - offset) as usize;
+    data.get_mut(start..start.checked_add(len as usize)?)
+}
+
+/// Every 4 bytes of data read as a little-endian `u32`, aligned or not => its occurrences.
+pub fn words(segments: &BTreeMap<u32, Vec<u8>>) -> HashMap<u32, usize> {
+    let mut words = HashMap::new();
+    for data in segments.values() {
+        for x in data.windows(4) {
+            *words
+                .entry(u32::from_le_bytes(x.try_into().unwrap()))
+                .or_default() += 1;
+        }
+    }
+    words
+}
+
+/// Memory argument of loads and stores of the mvp and simd.
+pub fn memarg(op: &Operator) -> Option<wasmparser::MemArg> {
+    match op {
+        Operator::I32Load { memarg }
+        | Operator::I64Load { memarg }
+        | Operator::F32Load { memarg }
+        | Operator::F64Load { memarg }
+        | Operator::I32Load8S { memarg }
+        | Operator::I32Load8U { memarg }
+        | Operator::I32Load16S { memarg }
+        | Operator::I32Load16U { memarg }
+        | Operator::I64Load8S { memarg }
+        | Operator::I64Load8U { memarg }
+        | Operator::I64Load16S { memarg }
+        | Operator::I64Load16U { memarg }
+        | Operator::I64Load32S { memarg }
+        | Operator::I64Load32U { memarg }
+        | Operator::I32Store { memarg }
+        | Operator::I64Store { memarg }
+        | Operator::F32Store { memarg }
+        | Operator::F64Store { memarg }
+        | Operator::I32Store8 { memarg }
+        | Operator::I32Store16 { memarg }
+        | Operator::I64Store8 { memarg }
+        | Operator::I64Store16 { memarg }
+        | Operator::I64Store32 { memarg }
+        | Operator::V128Load { memarg }
+        | Operator::V128Store { memarg } => Some(*memarg),
+        _ => None,
+    }
+}
+
+// a `&str` is deduplicated only when its pointer is always followed by the same length,
+// it overlaps no other string, nothing points into it and its pointer appears nowhere else
+// (data, globals, memory offsets). duplicates are zeroed, so that `compact` leaves them out.
+fn deduplicate(
+    segments: &mut BTreeMap<u32, Vec<u8>>,
+    references: &References,
+) -> HashMap<i32, i32> {
+    let mut pointers = references.fixed.iter().copied().collect::<BTreeSet<_>>();
+    pointers.extend(words(segments).into_keys());
+    let consts = references.consts.keys().copied().collect::<BTreeSet<_>>();
+
+    let pairs = references.pairs();
+
+    let mut strs = references
+        .consts
+        .iter()
+        .filter_map(|(ptr, nexts)| {
+            let len = (*nexts.first()?)?;
+            let end = ptr.checked_add(len)?;
+            let eligible = nexts.iter().all(|x| *x == Some(len))
+                && (MIN_DEDUP_LEN..=MAX_DEDUP_LEN).contains(&(len as usize))
+                && pointers.range(*ptr..end).next().is_none()
+                && consts.range(*ptr + 1..end).next().is_none()
+                && !overlaps(&pairs, *ptr, len);
+            Some((read(segments, *ptr, len)?.to_vec(), *ptr, eligible))
+        })
+        .collect::<Vec<_>>();
+    // the lowest address of the same content is kept
+    strs.sort();
+
+    let mut replaced = HashMap::new();
+    let mut kept: Option<(&[u8], u32)> = None;
+    for (content, ptr, eligible) in &strs {
+        match kept {
+            Some((bytes, at)) if bytes == &content[..] && *eligible => {
+                replaced.insert(*ptr as i32, at as i32);
+                read_mut(segments, *ptr, content.len() as u32)
+                    .unwrap()
+                    .fill(0);
+            }
+            Some((bytes, _)) if bytes == &content[..] => {}
+            _ => kept = Some((&content[..], *ptr)),
+        }
+    }
+    replaced
+}
+
+// merges segments with small gaps between and splits them at long zero runs.
+fn compact(segments: BTreeMap<u32, Vec<u8>>) -> Vec<(u32, Vec<u8>)> {
+    let mut merged = Vec::<(u32, Vec<u8>)>::new();
+    for (offset, data) in segments {
+        match merged.last_mut() {
+            Some((last_offset, last))
+                if (offset as usize) <= *last_offset as usize + last.len() + MIN_ZERO_GAP =>
+            {
+                last.resize(offset as usize - *last_offset as usize, 0);
+                last.extend(data);
+            }
+            _ => merged.push((offset, data)),
+        }
+    }
+
+    let mut ret = vec![];
+    for (offset, data) in merged {
+        let mut start = None;
+        let mut zeros = 0;
+        for (i, &byte) in data.iter().enumerate() {
+            if byte != 0 {
+                if let Some(s) = start
+                    && zeros > MIN_ZERO_GAP
+                {
+                    ret.push((offset + s as u32, data[s..i - zeros].to_vec()));
+                    start = None;
+                }
+                start.get_or_insert(i);
+                zeros = 0;
+            } else {
+                zeros += 1;
+            }
+        }
+        if let Some(s) = start {
+            ret.push((offset + s as u32, data[s..data.len() - zeros].to_vec()));
+        }
+    }
+    ret
+}
+
+#[test]
+fn compact_segments() {
+    let segments = BTreeMap::from([
+        (16, b"abc\0\0".to_vec()),
+        (24, b"def".to_vec()),
+        (100, [&[0; 4][..], b"ghi", &[0; 20], b"jkl"].concat()),
+    ]);
+    assert_eq!(
+        compact(segments),
+        vec![
+            (16, b"abc\0\0\0\0\0def".to_vec()),
+            (104, b"ghi".to_vec()),
+            (127, b"jkl".to_vec()),
+        ],
+    );
+
+    let text = b"called `Option::unwrap()` on a `None` value";
+    let mut segments = BTreeMap::from([(1000, [&text[..], &text[..]].concat())]);
+    let len = text.len() as u32;
+    let references = References {
+        consts: HashMap::from([(1000, vec![Some(len)]), (1000 + len, vec![Some(len)])]),
+        ..Default::default()
+    };
+    let consts = deduplicate(&mut segments, &references);
+    assert_eq!(consts, HashMap::from([(1000 + len as i32, 1000)]));
+    assert_eq!(compact(segments), vec![(1000, text.to_vec())]);
+}
+
+#[test]
+fn plan_with_data_count() {
+    use wasm_encoder::{
+        CodeSection, ConstExpr, DataCountSection, DataSection, Function, FunctionSection,
+        Instruction, MemorySection, MemoryType, Module, TypeSection,
+    };
+
+    let module = |init: bool| {
+        let mut module = Module::new();
+        let mut types = TypeSection::new();
+        types.function([], []);
+        module.section(&types);
+        let mut functions = FunctionSection::new();
+        functions.function(0);
+        module.section(&functions);
+        let mut memories = MemorySection::new();
+        memories.memory(MemoryType {
+            minimum: 1,
+            maximum: None,
+            memory64: false,
+            shared: false,
+        });
+        module.section(&memories);
+        module.section(&DataCountSection { count: 2 });
+        let mut code = CodeSection::new();
+        let mut function = Function::new([]);
+        if init {
+            function.instruction(&Instruction::DataDrop(1));
+        }
+        function.instruction(&Instruction::End);
+        code.function(&function);
+        module.section(&code);
+        let mut data = DataSection::new();
+        data.active(0, &ConstExpr::i32_const(16), b"abc".iter().copied());
+        data.active(0, &ConstExpr::i32_const(20), b"def".iter().copied());
+        module.section(&data);
+        module.finish()
+    };
+    let planned = plan(&module(false), false, false).unwrap().unwrap();
+    assert_eq!(planned.segments, vec![(16, b"abc\0def".to_vec())]);
+    assert!(plan(&module(true), false, false).unwrap().is_none());
+}
@@ -0,0 +1,696 @@
+// html minification without node, in place of html-minifier-terser.
+//
+// a tokenizer rather than a full html parser: good for what trunk writes, not for
+// arbitrary markup. inline css goes through lightningcss (`opt_css`) and inline
+// scripts through swc (`opt_js`).
+
+use anyhow::{bail, Result};
+
+use crate::error::{LocatedError, SourceLocation};
+use crate::opt_class::ClassMap;
+use crate::{opt_css, opt_js};
+
+/// Same switches as `html_minifier_option` in `sys::minifier`, all on by default.
+/// `decodeEntities`, `processConditionalComments` and `trimCustomFragments` have no
+/// counterpart: entities and conditional comments are kept as they are.
+#[derive(Debug, Clone)]
+pub struct HtmlOptions {
+    pub collapse_boolean_attributes: bool,
+    pub collapse_whitespace: bool,
+    pub minify_css: bool,
+    pub minify_js: bool,
+    pub remove_attribute_quotes: bool,
+    pub remove_comments: bool,
+    pub remove_empty_attributes: bool,
+    pub remove_optional_tags: bool,
+    pub remove_redundant_attributes: bool,
+    pub remove_script_type_attributes: bool,
+    pub remove_style_link_type_attributes: bool,
+    pub remove_tag_whitespace: bool,
+    pub sort_attributes: bool,
+    pub sort_class_name: bool,
+    pub use_short_doctype: bool,
+}
+
+impl Default for HtmlOptions {
+    fn default() -> Self {
+        Self {
+            collapse_boolean_attributes: true,
+            collapse_whitespace: true,
+            minify_css: true,
+            minify_js: true,
+            remove_attribute_quotes: true,
+            remove_comments: true,
+            remove_empty_attributes: true,
+            remove_optional_tags: true,
+            remove_redundant_attributes: true,
+            remove_script_type_attributes: true,
+            remove_style_link_type_attributes: true,
+            remove_tag_whitespace: true,
+            sort_attributes: true,
+            sort_class_name: true,
+            use_short_doctype: true,
+        }
+    }
+}
+
+impl HtmlOptions {
+    // prints tokens as they were, bar attribute quotes.
+    fn none() -> Self {
+        Self {
+            collapse_boolean_attributes: false,
+            collapse_whitespace: false,
+            minify_css: false,
+            minify_js: false,
+            remove_attribute_quotes: false,
+            remove_comments: false,
+            remove_empty_attributes: false,
+            remove_optional_tags: false,
+            remove_redundant_attributes: false,
+            remove_script_type_attributes: false,
+            remove_style_link_type_attributes: false,
+            remove_tag_whitespace: false,
+            sort_attributes: false,
+            sort_class_name: false,
+            use_short_doctype: false,
+        }
+    }
+}
+
+const VOID: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+// whitespace next to any other element can be dropped.
+const INLINE: &[&str] = &[
+    "a", "abbr", "acronym", "audio", "b", "bdi", "bdo", "big", "button", "cite", "code", "data",
+    "del", "dfn", "em", "font", "i", "img", "input", "ins", "kbd", "label", "mark", "math",
+    "meter", "noscript", "object", "output", "picture", "progress", "q", "rp", "rt", "ruby", "s",
+    "samp", "select", "small", "span", "strike", "strong", "sub", "sup", "svg", "textarea", "time",
+    "tt", "u", "var", "video", "wbr",
+];
+
+const BOOLEAN: &[&str] = &[
+    "allowfullscreen",
+    "async",
+    "autofocus",
+    "autoplay",
+    "checked",
+    "controls",
+    "default",
+    "defer",
+    "disabled",
+    "formnovalidate",
+    "hidden",
+    "inert",
+    "ismap",
+    "itemscope",
+    "loop",
+    "multiple",
+    "muted",
+    "nomodule",
+    "novalidate",
+    "open",
+    "playsinline",
+    "readonly",
+    "required",
+    "reversed",
+    "selected",
+];
+
+// a `<p>` is closed by any of these.
+const CLOSES_P: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "details",
+    "div",
+    "dl",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hgroup",
+    "hr",
+    "main",
+    "menu",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+const JS_TYPES: &[&str] = &["text/javascript", "application/javascript"];
+
+#[derive(Debug)]
+struct Tag {
+    // tag and attribute names are lowercased. the html parser does the same and
+    // restores the case of svg names by itself.
+    name: String,
+    attrs: Vec<(String, Option<String>)>,
+    self_closing: bool,
+}
+
+impl Tag {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(x, _)| x == name)
+            .map(|(_, value)| value.as_deref().unwrap_or(""))
+    }
+}
+
+#[derive(Debug)]
+enum Token {
+    Text(String),
+    // contents of `script`, `style`, `textarea` and `title`.
+    Raw(String),
+    Comment(String),
+    Doctype(String),
+    Start(Tag),
+    End(String),
+}
+
+pub fn minify_html(html: &str, options: &HtmlOptions) -> Result<String> {
+    let mut tokens = Tokenizer { html, pos: 0 }.tokenize()?;
+    if options.remove_comments {
+        // conditional comments and `<!--! ... -->` are meant to stay.
+        tokens.retain(|x| match x {
+            Token::Comment(comment) => {
+                comment.starts_with("<!--[if") || comment.starts_with("<!--!")
+            }
+            _ => true,
+        });
+    }
+    for i in 0..tokens.len() {
+        if let Token::Start(tag) = &mut tokens[i] {
+            rewrite_attrs(tag, options);
+        } else if let Token::Raw(raw) = &tokens[i]
+            && let Token::Start(tag) = &tokens[i - 1]
+            && let Some(minified) = minify_raw(tag, raw, options)
+        {
+            tokens[i] = Token::Raw(minified);
+        }
+    }
+    if options.collapse_whitespace {
+        collapse_whitespace(&mut tokens);
+    }
+    Ok(print(&tokens, options))
+}
+
+/// Classes in `class` attributes.
+pub fn classes(html: &str) -> Result<Vec<String>> {
+    let tokens = Tokenizer { html, pos: 0 }.tokenize()?;
+    Ok(tokens
+        .iter()
+        .filter_map(|x| match x {
+            Token::Start(tag) => tag.attr("class"),
+            _ => None,
+        })
+        .flat_map(str::split_ascii_whitespace)
+        .map(str::to_owned)
+        .collect())
+}
+
+/// Renames classes in `class` attributes and `style` elements, leaving the rest as is.
+pub fn rename_classes(html: &str, classes: &ClassMap) -> Result<String> {
+    let mut tokens = Tokenizer { html, pos: 0 }.tokenize()?;
+    for i in 0..tokens.len() {
+        if let Token::Start(tag) = &mut tokens[i] {
+            for (attr, value) in &mut tag.attrs {
+                if attr == "class" && let Some(value) = value {
+                    *value = value
+                        .split_ascii_whitespace()
+                        .map(|x| classes.get(x).unwrap_or(x))
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                }
+            }
+        } else if let Token::Raw(raw) = &tokens[i]
+            && let Token::Start(tag) = &tokens[i - 1]
+            && tag.name == "style"
+        {
+            tokens[i] = Token::Raw(classes.rename_css(raw));
+        }
+    }
+    Ok(print(&tokens, &HtmlOptions::none()))
+}
+
+struct Tokenizer<'a> {
+    html: &'a str,
+    pos: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn tokenize(mut self) -> Result<Vec<Token>> {
+        let mut tokens = vec![];
+        let mut text_start = 0;
+        while let Some(at) = self.html[self.pos..].find('<').map(|x| self.pos + x) {
+            let bytes = &self.html.as_bytes()[at + 1..];
+            let is_tag = match bytes {
+                [b'!', ..] => true,
+                [b'/', c, ..] | [c, ..] => c.is_ascii_alphabetic(),
+                [] => false,
+            };
+            if !is_tag {
+                self.pos = at + 1;
+                continue;
+            }
+            if text_start < at {
+                tokens.push(Token::Text(self.html[text_start..at].to_owned()));
+            }
+            self.pos = at;
+            let token = self.tag()?;
+            // raw text runs up to the matching end tag, whatever it contains.
+            if let Token::Start(tag) = &token
+                && !tag.self_closing
+                && matches!(&*tag.name, "script" | "style" | "textarea" | "title")
+            {
+                let end = format!("</{}", tag.name);
+                let Some(len) = self.html[self.pos..].to_ascii_lowercase().find(&end) else {
+                    bail!(self.error(at, format!("unclosed <{}>", tag.name)));
+                };
+                tokens.push(token);
+                tokens.push(Token::Raw(self.html[self.pos..self.pos + len].to_owned()));
+                self.pos += len;
+            } else {
+                tokens.push(token);
+            }
+            text_start = self.pos;
+        }
+        if text_start < self.html.len() {
+            tokens.push(Token::Text(self.html[text_start..].to_owned()));
+        }
+        Ok(tokens)
+    }
+
+    // at `<`
+    fn tag(&mut self) -> Result<Token> {
+        let html = self.html;
+        let start = self.pos;
+        let rest = &html[start..];
+        if rest.starts_with("<!--") {
+            let Some(len) = rest[4..].find("-->") else {
+                bail!(self.error(start, "unclosed comment".to_owned()));
+            };
+            self.pos += 4 + len + 3;
+            return Ok(Token::Comment(html[start..self.pos].to_owned()));
+        }
+        if rest.starts_with("<!") {
+            let Some(len) = rest.find('>') else {
+                bail!(self.error(start, "unclosed doctype".to_owned()));
+            };
+            self.pos += len + 1;
+            return Ok(Token::Doctype(html[start..self.pos].to_owned()));
+        }
+
+        let is_end = rest.starts_with("</");
+        self.pos += if is_end { 2 } else { 1 };
+        let name = self.take_while(|c| !(c.is_ascii_whitespace() || c == '/' || c == '>'));
+        let name = name.to_ascii_lowercase();
+        let mut attrs = vec![];
+        let mut self_closing = false;
+        loop {
+            self.take_while(|c| c.is_ascii_whitespace());
+            let rest = &html[self.pos..];
+            if rest.starts_with('>') {
+                self.pos += 1;
+                break;
+            }
+            if rest.starts_with("/>") {
+                self.pos += 2;
+                self_closing = true;
+                break;
+            }
+            if rest.is_empty() {
+                bail!(self.error(start, format!("unclosed <{name}>")));
+            }
+            if rest.starts_with('/') {
+                self.pos += 1;
+                continue;
+            }
+            let mut attr = self.take_while(|c| !(c.is_ascii_whitespace() || "=>/".contains(c)));
+            if attr.is_empty() {
+                // a stray `=`
+                attr = &rest[..1];
+                self.pos += 1;
+            }
+            let attr = attr.to_ascii_lowercase();
+            self.take_while(|c| c.is_ascii_whitespace());
+            let mut value = None;
+            if html[self.pos..].starts_with('=') {
+                self.pos += 1;
+                self.take_while(|c| c.is_ascii_whitespace());
+                let rest = &html[self.pos..];
+                value = Some(match rest.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let Some(len) = rest[1..].find(quote) else {
+                            bail!(self.error(self.pos, "unclosed attribute value".to_owned()));
+                        };
+                        self.pos += len + 2;
+                        rest[1..len + 1].to_owned()
+                    }
+                    _ => self
+                        .take_while(|c| !(c.is_ascii_whitespace() || c == '>'))
+                        .to_owned(),
+                });
+            }
+            attrs.push((attr, value));
+        }
+
+        Ok(if is_end {
+            Token::End(name)
+        } else {
+            Token::Start(Tag {
+                name,
+                attrs,
+                self_closing,
+            })
+        })
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let html = self.html;
+        let rest = &html[self.pos..];
+        let len = rest.find(|c| !f(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn error(&self, at: usize, message: String) -> LocatedError {
+        let before = &self.html[..at];
+        LocatedError {
+            location: SourceLocation {
+                line: before.matches('\n').count() + 1,
+                column: before.len() - before.rfind('\n').map_or(0, |x| x + 1),
+            },
+            message,
+        }
+    }
+}
+
+fn rewrite_attrs(tag: &mut Tag, options: &HtmlOptions) {
+    let name = tag.name.clone();
+    tag.attrs.retain(|(attr, value)| {
+        let value = value.as_deref().map(str::trim);
+        let is_type = |types: &[&str]| {
+            attr == "type" && value.is_some_and(|x| types.iter().any(|t| x.eq_ignore_ascii_case(t)))
+        };
+        let redundant = match (&*name, &**attr) {
+            ("script", "language") => true,
+            ("form", "method") => value.is_some_and(|x| x.eq_ignore_ascii_case("get")),
+            ("input", "type") => value.is_some_and(|x| x.eq_ignore_ascii_case("text")),
+            ("area", "shape") => value.is_some_and(|x| x.eq_ignore_ascii_case("rect")),
+            ("button", "type") => value.is_some_and(|x| x.eq_ignore_ascii_case("submit")),
+            _ => false,
+        };
+        let empty = value.map_or(true, str::is_empty)
+            && (matches!(&**attr, "class" | "id" | "style" | "title" | "lang" | "dir")
+                || attr.starts_with("on"));
+        !(options.remove_redundant_attributes && redundant
+            || options.remove_script_type_attributes && name == "script" && is_type(JS_TYPES)
+            || options.remove_style_link_type_attributes
+                && matches!(&*name, "style" | "link")
+                && is_type(&["text/css"])
+            || options.remove_empty_attributes && empty)
+    });
+    for (attr, value) in &mut tag.attrs {
+        // not `hidden=until-found`
+        let collapsible = value.as_deref().map_or(true, |x| {
+            let x = x.trim();
+            x.is_empty() || x.eq_ignore_ascii_case(attr)
+        });
+        if options.collapse_boolean_attributes && BOOLEAN.contains(&&**attr) && collapsible {
+            *value = None;
+        }
+        match (&**attr, value) {
+            ("class", Some(value)) => {
+                let mut classes = value.split_ascii_whitespace().collect::<Vec<_>>();
+                if options.sort_class_name {
+                    classes.sort_unstable();
+                }
+                *value = classes.join(" ");
+            }
+            ("style", Some(value)) if options.minify_css && !value.contains('&') => {
+                match opt_css::minify_style_attribute(value) {
+                    Ok(minified) => *value = minified,
+                    Err(e) => tracing::warn!("kept style attribute `{value}` as is: {e:#}"),
+                }
+            }
+            _ => {}
+        }
+    }
+    if options.sort_attributes {
+        tag.attrs.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+}
+
+// `None` keeps the contents as they are.
+fn minify_raw(tag: &Tag, raw: &str, options: &HtmlOptions) -> Option<String> {
+    let minified = match &*tag.name {
+        "script" if options.minify_js && tag.attr("src").is_none() => {
+            let module = match tag.attr("type").map(str::trim) {
+                None | Some("") => false,
+                Some(x) if JS_TYPES.iter().any(|t| x.eq_ignore_ascii_case(t)) => false,
+                Some("module") => true,
+                // json, import maps, templates, ...
+                Some(_) => return None,
+            };
+            opt_js::minify_script(raw, module)
+        }
+        "style" if options.minify_css => opt_css::minify_css(raw, "<style>"),
+        "title" if options.collapse_whitespace => {
+            return Some(raw.split_ascii_whitespace().collect::<Vec<_>>().join(" "));
+        }
+        _ => return None,
+    };
+    match minified {
+        // it must not end the element early.
+        Ok(minified)
+            if !minified
+                .to_ascii_lowercase()
+                .contains(&format!("</{}", tag.name)) =>
+        {
+            Some(minified)
+        }
+        Ok(_) => None,
+        Err(e) => {
+            tracing::warn!("kept contents of <{}> as is: {e:#}", tag.name);
+            None
+        }
+    }
+}
+
+fn collapse_whitespace(tokens: &mut Vec<Token>) {
+    let is_inline = |token: Option<&Token>| match token {
+        Some(Token::Start(Tag { name, .. }) | Token::End(name)) => INLINE.contains(&&**name),
+        Some(Token::Text(_) | Token::Raw(_) | Token::Comment(_)) => true,
+        Some(Token::Doctype(_)) | None => false,
+    };
+    let mut pre = 0usize;
+    for i in 0..tokens.len() {
+        match &tokens[i] {
+            Token::Start(tag) if tag.name == "pre" && !tag.self_closing => pre += 1,
+            Token::End(name) if name == "pre" => pre = pre.saturating_sub(1),
+            Token::Text(text) if pre == 0 => {
+                let trim_start = !is_inline(i.checked_sub(1).map(|i| &tokens[i]));
+                let trim_end = !is_inline(tokens.get(i + 1));
+                let mut collapsed = String::with_capacity(text.len());
+                let mut space = false;
+                for c in text.chars() {
+                    if c.is_ascii_whitespace() {
+                        space = true;
+                        continue;
+                    }
+                    if space && !(collapsed.is_empty() && trim_start) {
+                        collapsed.push(' ');
+                    }
+                    space = false;
+                    collapsed.push(c);
+                }
+                if space && !trim_end && !(collapsed.is_empty() && trim_start) {
+                    collapsed.push(' ');
+                }
+                tokens[i] = Token::Text(collapsed);
+            }
+            _ => {}
+        }
+    }
+    tokens.retain(|x| !matches!(x, Token::Text(text) if text.is_empty()));
+}
+
+// whether the tag can be left out given the token following it.
+fn is_optional(token: &Token, next: Option<&Token>) -> bool {
+    let starts =
+        |names: &[&str]| matches!(next, Some(Token::Start(tag)) if names.contains(&&*tag.name));
+    let ends_parent = matches!(next, Some(Token::End(_)) | None);
+    match token {
+        Token::Start(tag) if !tag.attrs.is_empty() => false,
+        Token::Start(tag) => match &*tag.name {
+            "html" => !matches!(next, Some(Token::Comment(_))),
+            "head" => {
+                matches!(next, Some(Token::Start(_)))
+                    || matches!(next, Some(Token::End(x)) if x == "head")
+            }
+            "body" => {
+                !matches!(next, Some(Token::Comment(_) | Token::Text(_)))
+                    && !starts(&["meta", "link", "script", "style", "template"])
+            }
+            _ => false,
+        },
+        Token::End(name) => match &**name {
+            "html" | "head" | "body" => !matches!(next, Some(Token::Comment(_) | Token::Text(_))),
+            "p" => starts(CLOSES_P),
+            "li" => starts(&["li"]) || ends_parent,
+            "dt" => starts(&["dt", "dd"]),
+            "dd" => starts(&["dt", "dd"]) || ends_parent,
+            "option" => starts(&["option", "optgroup"]) || ends_parent,
+            "tr" => starts(&["tr"]) || ends_parent,
+            "td" | "th" => starts(&["td", "th"]) || ends_parent,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn print(tokens: &[Token], options: &HtmlOptions) -> String {
+    let mut out = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if options.remove_optional_tags && is_optional(token, tokens.get(i + 1)) {
+            continue;
+        }
+        match token {
+            Token::Doctype(doctype)
+                if options.use_short_doctype
+                    && doctype
+                        .get(..14)
+                        .is_some_and(|x| x.eq_ignore_ascii_case("<!doctype html")) =>
+            {
+                out.push_str("<!doctype html>")
+            }
+            Token::Text(x) | Token::Raw(x) | Token::Comment(x) | Token::Doctype(x) => {
+                out.push_str(x)
+            }
+            Token::End(name) => {
+                out.push_str("</");
+                out.push_str(name);
+                out.push('>');
+            }
+            Token::Start(tag) => {
+                out.push('<');
+                out.push_str(&tag.name);
+                let mut quoted = false;
+                for (attr, value) in &tag.attrs {
+                    if !(quoted && options.remove_tag_whitespace) {
+                        out.push(' ');
+                    }
+                    out.push_str(attr);
+                    quoted = false;
+                    let Some(value) = value else { continue };
+                    out.push('=');
+                    let unquoted = options.remove_attribute_quotes
+                        && !value.is_empty()
+                        && !value.ends_with('/')
+                        && !value
+                            .contains(|c: char| c.is_ascii_whitespace() || "\"'=<>`".contains(c));
+                    if unquoted {
+                        out.push_str(value);
+                    } else if value.contains('"') && !value.contains('\'') {
+                        out.push('\'');
+                        out.push_str(value);
+                        out.push('\'');
+                        quoted = true;
+                    } else {
+                        out.push('"');
+                        out.push_str(&value.replace('"', "&#34;"));
+                        out.push('"');
+                        quoted = true;
+                    }
+                }
+                // only foreign elements such as svg need `/>`.
+                if tag.self_closing && !VOID.contains(&&*tag.name) {
+                    if !quoted && !tag.attrs.is_empty() {
+                        out.push(' ');
+                    }
+                    out.push('/');
+                }
+                out.push('>');
+            }
+        }
+    }
+    out
+}
+
+#[test]
+fn minify() {
+    let html = r#"<!DOCTYPE html>
+<html lang="en">
+  <head>
+    <meta charset="UTF-8" />
+    <meta name="viewport" content="width=device-width, initial-scale=1.0" />
+    <!-- styles -->
+    <link rel="stylesheet" type="text/css" href="/index-1a2b3c4d.css" />
+    <style type="text/css">
+      html { color: #ff0000; }
+    </style>
+    <title> Shoutter </title>
+  </head>
+  <body>
+    <ul class=" b  a ">
+      <li><input type="text" disabled="disabled" value=""></li>
+      <li>one <b>two</b>   three</li>
+    </ul>
+    <pre>  keep
+  this </pre>
+  </body>
+</html>
+"#;
+    assert_eq!(
+        minify_html(html, &HtmlOptions::default()).unwrap(),
+        concat!(
+            r#"<!doctype html><html lang=en><meta charset=UTF-8><meta content="width=device-width, initial-scale=1.0"name=viewport>"#,
+            r#"<link href=/index-1a2b3c4d.css rel=stylesheet><style>html{color:red}</style><title>Shoutter</title>"#,
+            r#"<ul class="a b"><li><input disabled value=""><li>one <b>two</b> three</ul><pre>  keep"#,
+            "\n  this </pre>",
+        )
+    );
+
+    let script = "<script type=module>\n  import init from '/a.js';\n  init();\n</script>";
+    let minified = minify_html(script, &HtmlOptions::default()).unwrap();
+    assert!(
+        minified.starts_with("<script type=module>import"),
+        "{minified}"
+    );
+    assert!(!minified.contains('\n'), "{minified}");
+
+    // classic scripts share their top level with other scripts
+    let script = "<script>\n  var counter = 0;\n  function bump() { counter += 1; }\n</script>";
+    let minified = minify_html(script, &HtmlOptions::default()).unwrap();
+    assert!(
+        minified.contains("counter") && minified.contains("bump"),
+        "{minified}"
+    );
+    let script =
+        "<script type=module>\n  const message = 'hi';\n  console.log(message);\n</script>";
+    let minified = minify_html(script, &HtmlOptions::default()).unwrap();
+    assert!(!minified.contains("message"), "{minified}");
+
+    let details = r#"<details hidden="until-found" open="open"></details>"#;
+    assert_eq!(
+        minify_html(details, &HtmlOptions::default()).unwrap(),
+        "<details hidden=until-found open></details>"
+    );
+}
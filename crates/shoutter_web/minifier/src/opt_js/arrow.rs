@@ -0,0 +1,894 @@
+// turns functions into arrow functions where nothing can tell them apart.
+//
+// names are resolved through module, function and block scopes. wherever that is not
+// tracked (catch clauses, loop heads, class names) a name resolves to an outer declaration
+// instead, which can only keep more functions as they are.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use swc_core::common::{BytePos, Span, DUMMY_SP};
+use swc_core::ecma::ast::{
+    ArrayPat, ArrowExpr, AssignExpr, AssignPatProp, BindingIdent, BlockStmt, BlockStmtOrExpr,
+    CallExpr, Callee, Class, Constructor, Decl, DefaultDecl, ExportDecl, ExportSpecifier, Expr,
+    FnDecl, FnExpr, ForInStmt, ForOfStmt, Function, GetterProp, Ident, ImportDecl, ImportSpecifier,
+    MemberExpr, MemberProp, MetaPropExpr, MetaPropKind, Module, ModuleDecl, ModuleExportName,
+    ModuleItem, NamedExport, NewExpr, Param, Pat, PatOrExpr, PropName, RestPat, ReturnStmt,
+    SetterProp, Stmt, Super, SwitchStmt, ThisExpr, UpdateExpr, VarDecl, VarDeclKind, VarDeclarator,
+};
+use swc_core::ecma::atoms::JsWord;
+use swc_core::ecma::visit::{Visit, VisitMut, VisitMutWith, VisitWith};
+
+/// Why a function is kept as it is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Skip {
+    /// may refer to itself by its name
+    Named,
+    Generator,
+    /// `this`, `super` or `new.target`, which arrow functions take from outside
+    This,
+    /// `arguments` along with parameters that can't be moved into the body
+    Arguments,
+    /// `new f`, `f.prototype` or `extends f`. functions passed elsewhere are assumed not
+    /// to be constructed there.
+    Constructor,
+    /// may be called before its declaration, which a `const` does not allow
+    Hoisted,
+    Reassigned,
+    Redeclared,
+    /// a module importing others may be imported back and called before it is evaluated
+    Exported,
+    Eval,
+}
+
+impl fmt::Display for Skip {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Skip::Named => "named function expression",
+            Skip::Generator => "generator",
+            Skip::This => "uses `this`, `super` or `new.target`",
+            Skip::Arguments => "uses `arguments` along with parameters other than plain names",
+            Skip::Constructor => "used as a constructor",
+            Skip::Hoisted => "may be called before its declaration",
+            Skip::Reassigned => "reassigned",
+            Skip::Redeclared => "declared more than once",
+            Skip::Exported => "exported from a module with imports",
+            Skip::Eval => "the module calls `eval`",
+        })
+    }
+}
+
+/// Turns function expressions into arrow functions and function declarations into
+/// `const`s of them, unless the rewrite could be observed (see [`Skip`]).
+pub struct FunctionToArrowFn {
+    /// function span => name of declarations, why it is kept
+    skipped: HashMap<Span, (Option<JsWord>, Skip)>,
+}
+
+impl FunctionToArrowFn {
+    pub fn new(m: &Module) -> Self {
+        let mut resolver = Resolver::default();
+        m.visit_with(&mut resolver);
+        Self {
+            skipped: resolver.finish(),
+        }
+    }
+
+    /// Functions kept as they are, in source order.
+    pub fn skipped(&self) -> Vec<(Span, Option<&JsWord>, Skip)> {
+        let mut skipped = self
+            .skipped
+            .iter()
+            .map(|(span, (name, skip))| (*span, name.as_ref(), *skip))
+            .collect::<Vec<_>>();
+        skipped.sort_by_key(|(span, ..)| span.lo);
+        skipped
+    }
+}
+
+impl VisitMut for FunctionToArrowFn {
+    fn visit_mut_expr(&mut self, n: &mut Expr) {
+        n.visit_mut_children_with(self);
+        let Expr::Fn(f) = n else { return };
+
+        if self.skipped.contains_key(&f.function.span) {
+            return;
+        }
+
+        let Some(arrow_fn) = map_function(*f.function.clone()) else { return };
+        *n = Expr::Arrow(arrow_fn);
+    }
+
+    fn visit_mut_decl(&mut self, n: &mut Decl) {
+        n.visit_mut_children_with(self);
+
+        let Decl::Fn(f) = n else { return };
+
+        if self.skipped.contains_key(&f.function.span) {
+            return;
+        }
+
+        let Some(arrow_fn) = map_function(*f.function.clone()) else { return };
+
+        let span = f.function.span;
+        let d = VarDeclarator {
+            span,
+            name: Pat::Ident(BindingIdent {
+                id: f.ident.clone(),
+                type_ann: None,
+            }),
+            init: Some(Box::new(Expr::Arrow(arrow_fn))),
+            definite: false,
+        };
+
+        *n = Decl::Var(Box::new(VarDecl {
+            span,
+            kind: VarDeclKind::Const,
+            declare: f.declare,
+            decls: vec![d],
+        }));
+    }
+}
+
+fn map_function(mut f: Function) -> Option<ArrowExpr> {
+    // replace `arguments` special identifier to rest parameter
+    // from: function() { d(arguments); }
+    // to  : function(...a) { d(a); }
+    // and moves parameters into the body
+    // from: function(x, y) { d(x, arguments); }
+    // to  : function(...a) { let [x, y] = a; d(x, a); }
+    let arg_replacement = JsWord::from("__minifier_arguments");
+    let mut arg_replacer = RenameArguments::new(arg_replacement.clone());
+    f.body.visit_mut_children_with(&mut arg_replacer);
+    if arg_replacer.have_arguments {
+        if !f.params.is_empty() {
+            // rejected by `Skip::Arguments`
+            let names = movable_params(&f)?;
+            let params = VarDeclarator {
+                span: DUMMY_SP,
+                name: Pat::Array(ArrayPat {
+                    span: DUMMY_SP,
+                    elems: names
+                        .into_iter()
+                        .map(|id| Some(Pat::Ident(BindingIdent { id, type_ann: None })))
+                        .collect(),
+                    optional: false,
+                    type_ann: None,
+                }),
+                init: Some(Box::new(Expr::Ident(Ident::new(
+                    arg_replacement.clone(),
+                    DUMMY_SP,
+                )))),
+                definite: false,
+            };
+            f.body.as_mut()?.stmts.insert(
+                0,
+                Stmt::Decl(Decl::Var(Box::new(VarDecl {
+                    span: DUMMY_SP,
+                    kind: VarDeclKind::Let,
+                    declare: false,
+                    decls: vec![params],
+                }))),
+            );
+            f.params.clear();
+        }
+        f.params.push(Param {
+            pat: Pat::Rest(RestPat {
+                arg: Box::new(Pat::Ident(BindingIdent {
+                    id: Ident::new(arg_replacement, DUMMY_SP),
+                    type_ann: None,
+                })),
+                span: DUMMY_SP,
+                dot3_token: DUMMY_SP,
+                type_ann: None,
+            }),
+            span: DUMMY_SP,
+            decorators: vec![],
+        })
+    }
+
+    // decorator is not allowed on arrow function.
+    // e.g. rejects: function(@a hoge) {}
+    let params = f
+        .params
+        .into_iter()
+        .map(|x| x.decorators.is_empty().then_some(x.pat))
+        .collect::<Option<Vec<_>>>()?;
+    let mut arrow = ArrowExpr {
+        span: f.span,
+        params,
+        body: Box::new(BlockStmtOrExpr::BlockStmt(f.body?)),
+        is_async: f.is_async,
+        // rejected by `Skip::Generator`
+        is_generator: false,
+        type_params: f.type_params,
+        return_type: f.return_type,
+    };
+
+    // from: () => { const arg_ident = init; return arg_ident; }
+    // to  : () => init;
+    // if let Some(BlockStmt { stmts: body, .. }) = &mut f.body
+    if let BlockStmtOrExpr::BlockStmt(BlockStmt { stmts: body, .. }) = &mut *arrow.body
+        && let [may_decl, may_ret] = &mut body[..]
+        && let Stmt::Decl(Decl::Var(box VarDecl { kind: VarDeclKind::Const, declare: false, decls, span: _  })) = may_decl
+        && let [VarDeclarator { name: Pat::Ident(BindingIdent { id: ref decl_name, type_ann: None }), init: Some(ref init), definite: false, .. }] = decls[..]
+        && let Stmt::Return(ReturnStmt { arg: Some(box Expr::Ident(arg_ident)), .. }) = may_ret
+        && arg_ident.sym == decl_name.sym
+    {
+        arrow.body = Box::new(BlockStmtOrExpr::Expr(init.clone()));
+    }
+
+    Some(arrow)
+}
+
+// parameters that can become `let [x, y] = arguments` at the top of the body: plain names,
+// without defaults, declared nowhere else in the body. `length` of the function becomes 0,
+// which wasm-bindgen glue never reads.
+fn movable_params(f: &Function) -> Option<Vec<Ident>> {
+    let mut names = Vec::<Ident>::new();
+    for param in &f.params {
+        let Pat::Ident(BindingIdent { id, type_ann: None }) = &param.pat else { return None };
+        if !param.decorators.is_empty() || names.iter().any(|x| x.sym == id.sym) {
+            return None;
+        }
+        names.push(id.clone());
+    }
+    let mut declared = vec![];
+    if let Some(body) = &f.body {
+        block(&body.stmts, &mut declared);
+        body.stmts.visit_with(&mut Vars(&mut declared));
+    }
+    if declared
+        .iter()
+        .any(|(name, _)| names.iter().any(|x| x.sym == *name))
+    {
+        return None;
+    }
+    Some(names)
+}
+
+/// find `arguments` identifier
+pub struct RenameArguments {
+    replacement: JsWord,
+    have_arguments: bool,
+}
+impl RenameArguments {
+    fn new(replacement: JsWord) -> Self {
+        Self {
+            replacement,
+            have_arguments: false,
+        }
+    }
+}
+// functions other than arrow functions have `arguments` of their own
+impl VisitMut for RenameArguments {
+    fn visit_mut_function(&mut self, _n: &mut Function) {
+        // stop propergation
+    }
+    fn visit_mut_getter_prop(&mut self, _n: &mut GetterProp) {
+        // stop propergation
+    }
+    fn visit_mut_setter_prop(&mut self, _n: &mut SetterProp) {
+        // stop propergation
+    }
+    fn visit_mut_constructor(&mut self, _n: &mut Constructor) {
+        // stop propergation
+    }
+    fn visit_mut_member_prop(&mut self, n: &mut MemberProp) {
+        if let MemberProp::Computed(c) = n {
+            c.visit_mut_with(self);
+        }
+    }
+    fn visit_mut_prop_name(&mut self, n: &mut PropName) {
+        if let PropName::Computed(c) = n {
+            c.visit_mut_with(self);
+        }
+    }
+    fn visit_mut_ident(&mut self, n: &mut Ident) {
+        if &*n.sym == "arguments" {
+            n.sym = self.replacement.clone();
+            self.have_arguments = true;
+        }
+    }
+}
+
+// finds what a function would take from outside as an arrow function.
+#[derive(Default)]
+struct Inspector {
+    this: bool,
+    arguments: bool,
+}
+
+impl Visit for Inspector {
+    fn visit_this_expr(&mut self, _: &ThisExpr) {
+        self.this = true;
+    }
+
+    fn visit_super(&mut self, _: &Super) {
+        self.this = true;
+    }
+
+    fn visit_meta_prop_expr(&mut self, n: &MetaPropExpr) {
+        self.this |= n.kind == MetaPropKind::NewTarget;
+    }
+
+    fn visit_ident(&mut self, n: &Ident) {
+        self.arguments |= &*n.sym == "arguments";
+    }
+
+    fn visit_member_prop(&mut self, n: &MemberProp) {
+        if let MemberProp::Computed(c) = n {
+            c.visit_with(self);
+        }
+    }
+
+    fn visit_prop_name(&mut self, n: &PropName) {
+        if let PropName::Computed(c) = n {
+            c.visit_with(self);
+        }
+    }
+
+    fn visit_function(&mut self, _: &Function) {}
+
+    fn visit_getter_prop(&mut self, _: &GetterProp) {}
+
+    fn visit_setter_prop(&mut self, _: &SetterProp) {}
+
+    fn visit_constructor(&mut self, _: &Constructor) {}
+}
+
+#[derive(Clone, Copy)]
+enum Binding {
+    /// function declaration, by the span of the function
+    Function(Span),
+    /// variable initialized with a function expression
+    Value(Span),
+    Other,
+}
+
+#[derive(Default)]
+struct Info {
+    /// a function expression or declaration
+    candidate: bool,
+    skip: Option<Skip>,
+    // of declarations only
+    name: Option<JsWord>,
+    end: BytePos,
+    exported: bool,
+    /// number of function declarations around the scope of the declaration
+    depth: usize,
+    /// references along with the function declarations around them, inside the scope
+    refs: Vec<(BytePos, Vec<Span>)>,
+}
+
+#[derive(Default)]
+struct Resolver {
+    scopes: Vec<HashMap<JsWord, Binding>>,
+    /// function declarations around, innermost last
+    fn_decls: Vec<Span>,
+    /// by the span of the function
+    functions: HashMap<Span, Info>,
+    // inside the left side of `[a, b] = ...`
+    assigning: bool,
+    imports: bool,
+    eval: bool,
+}
+
+impl Resolver {
+    fn enter(&mut self, bindings: Vec<(JsWord, Binding)>) {
+        let mut scope = HashMap::new();
+        for (name, binding) in bindings {
+            if let Binding::Function(span) = binding {
+                let info = self.functions.entry(span).or_default();
+                info.name = Some(name.clone());
+                info.end = span.hi;
+                info.depth = self.fn_decls.len();
+            }
+            if let Some(previous) = scope.insert(name, binding) {
+                for x in [previous, binding] {
+                    if let Binding::Function(span) = x {
+                        self.skip(span, Skip::Redeclared);
+                    }
+                }
+            }
+        }
+        self.scopes.push(scope);
+    }
+
+    fn leave(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn skip(&mut self, span: Span, skip: Skip) {
+        self.functions
+            .entry(span)
+            .or_default()
+            .skip
+            .get_or_insert(skip);
+    }
+
+    fn resolve(&self, name: &JsWord) -> Option<Binding> {
+        self.scopes.iter().rev().find_map(|x| x.get(name)).copied()
+    }
+
+    fn reference(&mut self, id: &Ident, assign: bool) {
+        self.eval |= &*id.sym == "eval";
+        let Some(Binding::Function(span)) = self.resolve(&id.sym) else { return };
+        let info = self.functions.entry(span).or_default();
+        if assign {
+            info.skip.get_or_insert(Skip::Reassigned);
+        }
+        let around = self.fn_decls[info.depth..].to_vec();
+        info.refs.push((id.span.lo, around));
+    }
+
+    // `new f`, `f.prototype` or `extends f`
+    fn construct(&mut self, mut e: &Expr) {
+        while let Expr::Paren(p) = e {
+            e = &p.expr;
+        }
+        match e {
+            Expr::Fn(f) => self.skip(f.function.span, Skip::Constructor),
+            Expr::Ident(i) => {
+                if let Some(Binding::Function(span) | Binding::Value(span)) = self.resolve(&i.sym) {
+                    self.skip(span, Skip::Constructor);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(mut self) -> HashMap<Span, (Option<JsWord>, Skip)> {
+        for info in self.functions.values_mut() {
+            if self.eval {
+                info.skip.get_or_insert(Skip::Eval);
+            }
+            if info.exported && self.imports {
+                info.skip.get_or_insert(Skip::Exported);
+            }
+        }
+        // a reference can't run before the declaration if it's inside the function itself,
+        // or after the declaration and only inside function declarations turned into `const`s.
+        loop {
+            let hoisted = self
+                .functions
+                .iter()
+                .filter(|(span, info)| {
+                    info.name.is_some()
+                        && info.skip.is_none()
+                        && info.refs.iter().any(|(at, around)| {
+                            !around.contains(span)
+                                && (*at < info.end
+                                    || around.iter().any(|x| {
+                                        self.functions.get(x).map_or(true, |x| x.skip.is_some())
+                                    }))
+                        })
+                })
+                .map(|(span, _)| *span)
+                .collect::<Vec<_>>();
+            if hoisted.is_empty() {
+                break;
+            }
+            for span in hoisted {
+                self.skip(span, Skip::Hoisted);
+            }
+        }
+        self.functions
+            .into_iter()
+            .filter(|(_, info)| info.candidate)
+            .filter_map(|(span, info)| Some((span, (info.name, info.skip?))))
+            .collect()
+    }
+}
+
+// names bound by a pattern.
+#[derive(Default)]
+struct Bound(Vec<JsWord>);
+
+impl Visit for Bound {
+    fn visit_binding_ident(&mut self, n: &BindingIdent) {
+        self.0.push(n.id.sym.clone());
+    }
+
+    fn visit_assign_pat_prop(&mut self, n: &AssignPatProp) {
+        self.0.push(n.key.sym.clone());
+    }
+
+    // default values and computed keys
+    fn visit_expr(&mut self, _: &Expr) {}
+}
+
+fn bound<T: VisitWith<Bound>>(n: &T, out: &mut Vec<(JsWord, Binding)>) {
+    let mut bound = Bound::default();
+    n.visit_with(&mut bound);
+    out.extend(bound.0.into_iter().map(|x| (x, Binding::Other)));
+}
+
+fn declarator(d: &VarDeclarator, out: &mut Vec<(JsWord, Binding)>) {
+    let mut init = d.init.as_deref();
+    while let Some(Expr::Paren(p)) = init {
+        init = Some(&p.expr);
+    }
+    match (&d.name, init) {
+        (Pat::Ident(i), Some(Expr::Fn(f))) => {
+            out.push((i.id.sym.clone(), Binding::Value(f.function.span)))
+        }
+        (name, _) => bound(name, out),
+    }
+}
+
+// block scoped declarations. `var`s are left to `Vars`.
+fn declare(decl: &Decl, out: &mut Vec<(JsWord, Binding)>) {
+    match decl {
+        Decl::Fn(f) => out.push((f.ident.sym.clone(), Binding::Function(f.function.span))),
+        Decl::Class(c) => out.push((c.ident.sym.clone(), Binding::Other)),
+        Decl::Var(v) if v.kind != VarDeclKind::Var => {
+            for d in &v.decls {
+                declarator(d, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn block(stmts: &[Stmt], out: &mut Vec<(JsWord, Binding)>) {
+    for stmt in stmts {
+        if let Stmt::Decl(decl) = stmt {
+            declare(decl, out);
+        }
+    }
+}
+
+// `var`s of a function, in nested blocks too.
+struct Vars<'a>(&'a mut Vec<(JsWord, Binding)>);
+
+impl Visit for Vars<'_> {
+    fn visit_var_decl(&mut self, n: &VarDecl) {
+        if n.kind == VarDeclKind::Var {
+            for d in &n.decls {
+                declarator(d, self.0);
+            }
+        }
+    }
+
+    fn visit_function(&mut self, _: &Function) {}
+
+    fn visit_class(&mut self, _: &Class) {}
+
+    fn visit_expr(&mut self, _: &Expr) {}
+}
+
+impl Visit for Resolver {
+    fn visit_module(&mut self, n: &Module) {
+        let mut bindings = vec![];
+        for item in &n.body {
+            match item {
+                ModuleItem::Stmt(Stmt::Decl(decl))
+                | ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(ExportDecl { decl, .. })) => {
+                    declare(decl, &mut bindings)
+                }
+                ModuleItem::ModuleDecl(ModuleDecl::Import(import)) => {
+                    self.imports = true;
+                    for specifier in &import.specifiers {
+                        let local = match specifier {
+                            ImportSpecifier::Named(x) => &x.local,
+                            ImportSpecifier::Default(x) => &x.local,
+                            ImportSpecifier::Namespace(x) => &x.local,
+                        };
+                        bindings.push((local.sym.clone(), Binding::Other));
+                    }
+                }
+                ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultDecl(x)) => {
+                    let ident = match &x.decl {
+                        DefaultDecl::Fn(f) => &f.ident,
+                        DefaultDecl::Class(c) => &c.ident,
+                        _ => &None,
+                    };
+                    if let Some(ident) = ident {
+                        bindings.push((ident.sym.clone(), Binding::Other));
+                    }
+                }
+                ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(NamedExport {
+                    src: Some(_),
+                    ..
+                }))
+                | ModuleItem::ModuleDecl(ModuleDecl::ExportAll(_)) => self.imports = true,
+                _ => {}
+            }
+        }
+        n.body.visit_with(&mut Vars(&mut bindings));
+
+        self.enter(bindings);
+        n.body.visit_with(self);
+        self.leave();
+    }
+
+    fn visit_import_decl(&mut self, _: &ImportDecl) {}
+
+    fn visit_export_decl(&mut self, n: &ExportDecl) {
+        if let Decl::Fn(f) = &n.decl {
+            self.functions.entry(f.function.span).or_default().exported = true;
+        }
+        n.visit_children_with(self);
+    }
+
+    // `export { f }` runs nothing where it is
+    fn visit_named_export(&mut self, n: &NamedExport) {
+        if n.src.is_some() {
+            return;
+        }
+        for specifier in &n.specifiers {
+            if let ExportSpecifier::Named(x) = specifier
+                && let ModuleExportName::Ident(orig) = &x.orig
+                && let Some(Binding::Function(span)) = self.resolve(&orig.sym)
+            {
+                self.functions.entry(span).or_default().exported = true;
+            }
+        }
+    }
+
+    fn visit_call_expr(&mut self, n: &CallExpr) {
+        self.imports |= matches!(n.callee, Callee::Import(_));
+        n.visit_children_with(self);
+    }
+
+    fn visit_expr(&mut self, n: &Expr) {
+        if let Expr::Fn(f) = n {
+            self.functions.entry(f.function.span).or_default().candidate = true;
+        }
+        n.visit_children_with(self);
+    }
+
+    fn visit_fn_decl(&mut self, n: &FnDecl) {
+        self.functions.entry(n.function.span).or_default().candidate = true;
+        self.fn_decls.push(n.function.span);
+        n.function.visit_with(self);
+        self.fn_decls.pop();
+    }
+
+    // the name of a function expression is visible only inside
+    fn visit_fn_expr(&mut self, n: &FnExpr) {
+        if n.ident.is_some() {
+            self.skip(n.function.span, Skip::Named);
+        }
+        let name = n.ident.iter().map(|x| (x.sym.clone(), Binding::Other));
+        self.enter(name.collect());
+        n.function.visit_with(self);
+        self.leave();
+    }
+
+    fn visit_function(&mut self, n: &Function) {
+        if n.is_generator {
+            self.skip(n.span, Skip::Generator);
+        }
+        let mut inspector = Inspector::default();
+        n.params.visit_with(&mut inspector);
+        n.body.visit_with(&mut inspector);
+        if inspector.this {
+            self.skip(n.span, Skip::This);
+        }
+        if inspector.arguments && !n.params.is_empty() && movable_params(n).is_none() {
+            self.skip(n.span, Skip::Arguments);
+        }
+
+        let mut bindings = vec![];
+        bound(&n.params, &mut bindings);
+        if let Some(body) = &n.body {
+            block(&body.stmts, &mut bindings);
+            body.stmts.visit_with(&mut Vars(&mut bindings));
+        }
+        self.enter(bindings);
+        n.decorators.visit_with(self);
+        n.params.visit_with(self);
+        if let Some(body) = &n.body {
+            body.stmts.visit_with(self);
+        }
+        self.leave();
+    }
+
+    fn visit_arrow_expr(&mut self, n: &ArrowExpr) {
+        let mut bindings = vec![];
+        bound(&n.params, &mut bindings);
+        if let BlockStmtOrExpr::BlockStmt(body) = &*n.body {
+            block(&body.stmts, &mut bindings);
+            body.stmts.visit_with(&mut Vars(&mut bindings));
+        }
+        self.enter(bindings);
+        n.params.visit_with(self);
+        match &*n.body {
+            BlockStmtOrExpr::BlockStmt(body) => body.stmts.visit_with(self),
+            BlockStmtOrExpr::Expr(e) => e.visit_with(self),
+        }
+        self.leave();
+    }
+
+    fn visit_block_stmt(&mut self, n: &BlockStmt) {
+        let mut bindings = vec![];
+        block(&n.stmts, &mut bindings);
+        self.enter(bindings);
+        n.stmts.visit_with(self);
+        self.leave();
+    }
+
+    fn visit_switch_stmt(&mut self, n: &SwitchStmt) {
+        n.discriminant.visit_with(self);
+        let mut bindings = vec![];
+        for case in &n.cases {
+            block(&case.cons, &mut bindings);
+        }
+        self.enter(bindings);
+        n.cases.visit_with(self);
+        self.leave();
+    }
+
+    fn visit_class(&mut self, n: &Class) {
+        if let Some(super_class) = &n.super_class {
+            self.construct(super_class);
+        }
+        n.visit_children_with(self);
+    }
+
+    fn visit_new_expr(&mut self, n: &NewExpr) {
+        self.construct(&n.callee);
+        n.visit_children_with(self);
+    }
+
+    fn visit_member_expr(&mut self, n: &MemberExpr) {
+        if let MemberProp::Ident(prop) = &n.prop
+            && &*prop.sym == "prototype"
+        {
+            self.construct(&n.obj);
+        }
+        n.visit_children_with(self);
+    }
+
+    fn visit_member_prop(&mut self, n: &MemberProp) {
+        if let MemberProp::Computed(c) = n {
+            c.visit_with(self);
+        }
+    }
+
+    fn visit_prop_name(&mut self, n: &PropName) {
+        if let PropName::Computed(c) = n {
+            c.visit_with(self);
+        }
+    }
+
+    fn visit_ident(&mut self, n: &Ident) {
+        self.reference(n, false);
+    }
+
+    // declarations unless assigning
+    fn visit_binding_ident(&mut self, n: &BindingIdent) {
+        if self.assigning {
+            self.reference(&n.id, true);
+        }
+    }
+
+    fn visit_assign_pat_prop(&mut self, n: &AssignPatProp) {
+        if self.assigning {
+            self.reference(&n.key, true);
+        }
+        n.value.visit_with(self);
+    }
+
+    fn visit_assign_expr(&mut self, n: &AssignExpr) {
+        match &n.left {
+            PatOrExpr::Expr(box Expr::Ident(i))
+            | PatOrExpr::Pat(box Pat::Expr(box Expr::Ident(i))) => self.reference(i, true),
+            PatOrExpr::Pat(pat) => {
+                let assigning = std::mem::replace(&mut self.assigning, true);
+                pat.visit_with(self);
+                self.assigning = assigning;
+            }
+            PatOrExpr::Expr(e) => e.visit_with(self),
+        }
+        n.right.visit_with(self);
+    }
+
+    fn visit_update_expr(&mut self, n: &UpdateExpr) {
+        match &*n.arg {
+            Expr::Ident(i) => self.reference(i, true),
+            arg => arg.visit_with(self),
+        }
+    }
+
+    // `for (f of ...)`. declarations there are taken as assignments to outer names.
+    fn visit_for_in_stmt(&mut self, n: &ForInStmt) {
+        let assigning = std::mem::replace(&mut self.assigning, true);
+        n.left.visit_with(self);
+        self.assigning = assigning;
+        n.right.visit_with(self);
+        n.body.visit_with(self);
+    }
+
+    fn visit_for_of_stmt(&mut self, n: &ForOfStmt) {
+        let assigning = std::mem::replace(&mut self.assigning, true);
+        n.left.visit_with(self);
+        self.assigning = assigning;
+        n.right.visit_with(self);
+        n.body.visit_with(self);
+    }
+}
+
+#[test]
+fn keep_unsafe_functions() {
+    use swc_core::common::sync::Lrc;
+    use swc_core::common::SourceMap;
+
+    let js = r#"
+        early();
+        function early() {}
+        function self() { return this; }
+        function* numbers() { yield 1; }
+        function rest({ a }) { return arguments; }
+        function Point() {}
+        new Point();
+        function reassigned() {}
+        reassigned = null;
+        function callsLater() { return later(); }
+        function later() {}
+        function plain() { return arguments.length; }
+        export const target = function () { return new.target; };
+        export const identity = function (x) { return x; };
+        plain(callsLater());
+    "#;
+    let cm: Lrc<SourceMap> = Default::default();
+    let module = super::parse(&cm, "in.js", js.to_owned()).unwrap();
+    let arrows = FunctionToArrowFn::new(&module);
+    let skipped = arrows
+        .skipped()
+        .into_iter()
+        .map(|(_, name, skip)| (name.map(|x| &**x), skip))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        skipped,
+        [
+            (Some("early"), Skip::Hoisted),
+            (Some("self"), Skip::This),
+            (Some("numbers"), Skip::Generator),
+            (Some("rest"), Skip::Arguments),
+            (Some("Point"), Skip::Constructor),
+            (Some("reassigned"), Skip::Reassigned),
+            (Some("later"), Skip::Hoisted),
+            (None, Skip::This),
+        ]
+    );
+
+    let optimized = super::optimize_js(js, None, &Default::default()).unwrap();
+    assert!(optimized.contains("function later()"), "{optimized}");
+    assert!(!optimized.contains("function plain"), "{optimized}");
+    assert!(!optimized.contains("function callsLater"), "{optimized}");
+    assert!(!optimized.contains("function(x)"), "{optimized}");
+
+    // `const ret = ...; return f(ret);` keeps calling `f`
+    let optimized =
+        super::optimize_js(include_str!("corpus/glue.js"), None, &Default::default()).unwrap();
+    assert!(optimized.contains("addHeapObject(ret)"), "{optimized}");
+    assert!(optimized.contains("takeObject(ret)"), "{optimized}");
+
+    // an object literal would be read as a block
+    let js = "export const pair = function (a) { const x = { a }; return x; };";
+    let optimized = super::optimize_js(js, None, &Default::default()).unwrap();
+    let body = optimized.split("=>").nth(1).unwrap().trim_start();
+    assert!(body.starts_with("({"), "{optimized}");
+}
+
+#[test]
+fn move_params_for_arguments() {
+    let js = r#"
+        export const f = function (a, b) { return () => [a, b, arguments.length]; };
+        export const g = function (a) { var a; return arguments; };
+    "#;
+    let optimized = super::optimize_js(js, None, &Default::default()).unwrap();
+    assert!(
+        optimized.contains("let [a, b] = __minifier_arguments;"),
+        "{optimized}"
+    );
+    assert!(
+        optimized.contains("[a, b, __minifier_arguments.length]"),
+        "{optimized}"
+    );
+    // `a` is declared again in the body
+    assert_eq!(optimized.matches("let [").count(), 1, "{optimized}");
+}
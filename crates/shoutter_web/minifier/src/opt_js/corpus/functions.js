@@ -0,0 +1,93 @@
+// what `FunctionToArrowFn` must keep or may convert.
+
+function counter() {
+    let n = 0;
+    return () => ++n;
+}
+const next = counter();
+log(next(), next());
+
+log(early());
+function early() {
+    return 'hoisted';
+}
+
+function Point(x, y) {
+    this.x = x;
+    this.y = y;
+}
+Point.prototype.sum = function () {
+    return this.x + this.y;
+};
+log(new Point(1, 2).sum());
+
+function Plain() {}
+log(typeof new Plain());
+
+function variadic() {
+    return Array.prototype.slice.call(arguments).join(',');
+}
+log(variadic(1, 2, 3));
+
+function named(a, b) {
+    const inner = () => arguments.length;
+    return [a, b, inner()].join(' ');
+}
+log(named(1), named(1, 2, 3));
+
+function defaults(a = 1) {
+    return [a, arguments.length];
+}
+log(defaults(), defaults(2));
+
+function* numbers() {
+    yield 1;
+    yield 2;
+}
+log([...numbers()].join(','));
+
+const object = {
+    name: 'object',
+    method: function () {
+        return this.name;
+    },
+    plain: function () {
+        return typeof this;
+    },
+};
+log(object.method(), object.plain());
+
+function later() {
+    return helper();
+}
+function helper() {
+    return 'helper';
+}
+log(later());
+
+function pair(a, b) {
+    const result = { a, b };
+    return result;
+}
+log(JSON.stringify(pair(1, 2)));
+
+function last(a, b) {
+    const result = (log(a), b);
+    return result;
+}
+log(last(1, 2));
+
+function swap() {
+    return 'swap';
+}
+swap = function () {
+    return 'swapped';
+};
+log(swap());
+
+log([1, 2].map(function (x) {
+    return x * 2;
+}));
+log(new (function () {})() instanceof Object);
+log(typeof function () {}.prototype);
+log((async function () {})() instanceof Promise);
@@ -0,0 +1,20 @@
+// what `InternString` must leave alone or may move into `const`s.
+'use strict';
+
+const kinds = ['undefined', 'function', 'object'];
+
+function kindOf(x) {
+    const t = typeof x;
+    if (t === 'undefined') return 'undefined';
+    if (t === 'function') return 'function';
+    if (t === 'object') return x === null ? 'null' : 'object';
+    return t;
+}
+log(kindOf(undefined), kindOf(log), kindOf({}), kindOf(null), kindOf(1));
+
+const table = { 'undefined': 1, function: 2 };
+log(table['undefined'], table['function'], Object.keys(table).join());
+
+const repeated = ['undefined', 'undefined', 'function', 'function', 'object', 'object'];
+log(repeated.filter((x) => kinds.includes(x)).length);
+log(`undefined ${'function'}`, this === undefined);
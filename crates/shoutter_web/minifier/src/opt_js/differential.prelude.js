@@ -0,0 +1,67 @@
+// globals for the differential test corpus, which boa does not provide.
+
+globalThis.__output = [];
+globalThis.log = (...args) => {
+    __output.push(args.map((x) => (typeof x === 'string' ? x : JSON.stringify(x))).join(' '));
+};
+
+// utf-8 only, ignoring every option
+globalThis.TextEncoder = class {
+    encode(s) {
+        const out = [];
+        for (const c of s) {
+            const p = c.codePointAt(0);
+            if (p < 0x80) {
+                out.push(p);
+            } else if (p < 0x800) {
+                out.push(0xc0 | (p >> 6), 0x80 | (p & 63));
+            } else if (p < 0x10000) {
+                out.push(0xe0 | (p >> 12), 0x80 | ((p >> 6) & 63), 0x80 | (p & 63));
+            } else {
+                out.push(0xf0 | (p >> 18), 0x80 | ((p >> 12) & 63), 0x80 | ((p >> 6) & 63), 0x80 | (p & 63));
+            }
+        }
+        return new Uint8Array(out);
+    }
+
+    encodeInto(s, view) {
+        const bytes = this.encode(s);
+        view.set(bytes.subarray(0, view.length));
+        return { read: s.length, written: Math.min(bytes.length, view.length) };
+    }
+};
+
+globalThis.TextDecoder = class {
+    decode(bytes = new Uint8Array(0)) {
+        let s = '';
+        for (let i = 0; i < bytes.length; ) {
+            const b = bytes[i];
+            const n = b < 0x80 ? 1 : b < 0xe0 ? 2 : b < 0xf0 ? 3 : 4;
+            let p = n === 1 ? b : b & (0x7f >> n);
+            for (let j = 1; j < n; j++) {
+                p = (p << 6) | (bytes[i + j] & 63);
+            }
+            s += String.fromCodePoint(p);
+            i += n;
+        }
+        return s;
+    }
+};
+
+// "bytes" of a module are a function from imports to exports
+globalThis.WebAssembly = {
+    Module: class {
+        constructor(bytes) {
+            this.bytes = bytes;
+        }
+    },
+    Instance: class {
+        constructor(module, imports) {
+            this.exports = module.bytes(imports);
+        }
+    },
+    instantiate: async (bytes, imports) => {
+        const module = new WebAssembly.Module(bytes);
+        return { instance: new WebAssembly.Instance(module, imports), module };
+    },
+};
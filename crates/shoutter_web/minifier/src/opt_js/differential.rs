@@ -0,0 +1,100 @@
+// runs `corpus/*.js` in boa before and after `optimize_js` and compares what they log,
+// with and without swc's minifier.
+//
+// every snippet is an es module calling `log(...)`, with globals boa lacks given by
+// `differential.prelude.js`. `shoutter_web.js` is the app's glue as trunk writes it, which
+// only has to evaluate. `<name>.terser.js`, written by `just terser-corpus`, is compared
+// as well where it exists, as it isn't committed.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use boa_engine::builtins::promise::PromiseState;
+use boa_engine::module::Module;
+use boa_engine::{Context, JsError, JsResult, Source};
+
+use super::{optimize_js, JsOptions};
+
+const PRELUDE: &str = include_str!("differential.prelude.js");
+
+// logged lines, followed by the error if the module threw.
+fn run(js: &str) -> String {
+    let mut context = Context::default();
+    context.eval(Source::from_bytes(PRELUDE)).unwrap();
+
+    let evaluated = evaluate(js, &mut context);
+    let output = context
+        .eval(Source::from_bytes("__output.join('\\n')"))
+        .and_then(|x| x.to_string(&mut context))
+        .unwrap()
+        .to_std_string_escaped();
+    match evaluated {
+        Ok(()) => output,
+        Err(e) => format!("{output}\nthrew {e}"),
+    }
+}
+
+fn evaluate(js: &str, context: &mut Context<'_>) -> JsResult<()> {
+    let module = Module::parse(Source::from_bytes(js), None, context)?;
+    let promise = module.load_link_evaluate(context)?;
+    context.run_jobs();
+    match promise.state()? {
+        PromiseState::Fulfilled(_) => Ok(()),
+        PromiseState::Rejected(e) => Err(JsError::from_opaque(e)),
+        PromiseState::Pending => panic!("module never finished evaluating"),
+    }
+}
+
+fn corpus() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/opt_js/corpus");
+    let mut paths = fs::read_dir(dir)
+        .unwrap()
+        .map(|x| x.unwrap().path())
+        .filter(|x| {
+            let name = x.file_name().unwrap().to_string_lossy();
+            name.ends_with(".js") && !name.ends_with(".terser.js")
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn same_output_after_transforms() {
+    let paths = corpus();
+    assert!(!paths.is_empty());
+    for path in paths {
+        let js = fs::read_to_string(&path).unwrap();
+        let expected = run(&js);
+        assert!(
+            !expected.contains("threw"),
+            "{}: {expected}",
+            path.display()
+        );
+
+        let minified = JsOptions {
+            minify: true,
+            ..JsOptions::skipping(&[]).unwrap()
+        };
+        for options in [
+            JsOptions::default(),
+            JsOptions::skipping(&[]).unwrap(),
+            minified,
+        ] {
+            let optimized = optimize_js(js.clone(), None, &options).unwrap();
+            assert_eq!(
+                run(&optimized),
+                expected,
+                "{} with {options:?}:\n{optimized}",
+                path.display(),
+            );
+        }
+        let terser = path.with_extension("terser.js");
+        match fs::read_to_string(&terser) {
+            Ok(minified) => {
+                assert_eq!(run(&minified), expected, "{} by terser", path.display())
+            }
+            Err(_) => eprintln!("no {}, run `just terser-corpus`", terser.display()),
+        }
+    }
+}
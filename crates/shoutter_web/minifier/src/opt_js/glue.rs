@@ -0,0 +1,830 @@
+// passes tailored to wasm-bindgen glue.
+// names are compared as strings without scope analysis, so each pass gives up on a name
+// declared more than once or shadowed anywhere.
+
+use std::collections::{HashMap, HashSet};
+
+use swc_core::common::{Span, DUMMY_SP};
+use swc_core::ecma::ast::{
+    ArrowExpr, AssignExpr, AssignPatProp, AwaitExpr, BinExpr, BinaryOp, BindingIdent, BlockStmt,
+    CallExpr, Callee, CatchClause, Class, ClassDecl, ClassExpr, Decl, ExportDecl, Expr,
+    ExprOrSpread, ExprStmt, FnDecl, FnExpr, ForInStmt, ForOfStmt, ForStmt, Function, Ident, IfStmt,
+    ImportSpecifier, Lit, MemberExpr, MemberProp, MetaPropExpr, Module, ModuleDecl, ModuleItem,
+    NewExpr, Number, ParenExpr, Pat, PatOrExpr, Prop, PropName, PropOrSpread, ReturnStmt, Stmt,
+    Str, Super, ThisExpr, UnaryExpr, UnaryOp, UpdateExpr, VarDeclKind, YieldExpr,
+};
+use swc_core::ecma::atoms::JsWord;
+use swc_core::ecma::visit::{Visit, VisitMut, VisitMutWith, VisitWith};
+
+/// Turns module level `var`s into `let`, or `const` if never reassigned.
+/// Only those preceded by nothing but declarations, so that no code can read them before
+/// their initialization.
+pub struct VarToLet;
+
+impl VisitMut for VarToLet {
+    fn visit_mut_module(&mut self, m: &mut Module) {
+        let names = Names::collect(m);
+        for (at, item) in m.body.iter_mut().enumerate() {
+            if !is_inert(item, &names, at) {
+                break;
+            }
+            let ModuleItem::Stmt(Stmt::Decl(Decl::Var(var))) = item else { continue };
+            if var.kind != VarDeclKind::Var {
+                continue;
+            }
+            let idents = var
+                .decls
+                .iter()
+                .map(|x| match &x.name {
+                    Pat::Ident(i) => Some((&i.id.sym, x.init.is_some())),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>();
+            let Some(idents) = idents else { continue };
+            if idents
+                .iter()
+                .any(|(name, _)| names.decls.get(*name) != Some(&1))
+            {
+                continue;
+            }
+            let constant = idents
+                .iter()
+                .all(|(name, init)| *init && !names.assigned.contains(*name));
+            var.kind = if constant {
+                VarDeclKind::Const
+            } else {
+                VarDeclKind::Let
+            };
+        }
+    }
+}
+
+/// Inlines module level functions called only once whose body is a single `return`
+/// (or empty), when the arguments are literals or variables never reassigned.
+pub struct InlineFunctions;
+
+struct Inlinable {
+    params: Vec<JsWord>,
+    /// parameters referenced in the body
+    used: HashSet<JsWord>,
+    /// `None` for an empty body
+    body: Option<Box<Expr>>,
+}
+
+impl VisitMut for InlineFunctions {
+    fn visit_mut_module(&mut self, m: &mut Module) {
+        let names = Names::collect(m);
+        let mut candidates = HashMap::new();
+        for item in &m.body {
+            if let ModuleItem::Stmt(Stmt::Decl(Decl::Fn(f))) = item
+                && let Some(inlinable) = inlinable(f, &names)
+            {
+                candidates.insert(f.ident.sym.clone(), inlinable);
+            }
+        }
+        // a body must not change once it is copied
+        let calling_others = candidates
+            .iter()
+            .filter(|(_, f)| {
+                f.body.as_ref().map_or(false, |body| {
+                    let mut inspector = Inspector::default();
+                    body.visit_with(&mut inspector);
+                    inspector.idents.iter().any(|x| candidates.contains_key(x))
+                })
+            })
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        for name in calling_others {
+            candidates.remove(&name);
+        }
+
+        let mut inliner = Inliner {
+            candidates,
+            names: &names,
+            inlined: HashSet::new(),
+        };
+        m.body.visit_mut_with(&mut inliner);
+        let inlined = inliner.inlined;
+        m.body.retain(|item| {
+            !matches!(item, ModuleItem::Stmt(Stmt::Decl(Decl::Fn(f))) if inlined.contains(&f.ident.sym))
+        });
+    }
+}
+
+fn inlinable(f: &FnDecl, names: &Names) -> Option<Inlinable> {
+    let function = &f.function;
+    if function.is_async
+        || function.is_generator
+        || names.decls.get(&f.ident.sym) != Some(&1)
+        || names.refs(&f.ident.sym).len() != 1
+    {
+        return None;
+    }
+    let params = function
+        .params
+        .iter()
+        .map(|x| match &x.pat {
+            Pat::Ident(BindingIdent { id, type_ann: None }) if x.decorators.is_empty() => {
+                Some(id.sym.clone())
+            }
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if params.iter().collect::<HashSet<_>>().len() != params.len() {
+        return None;
+    }
+    let body = match &function.body.as_ref()?.stmts[..] {
+        [] | [Stmt::Return(ReturnStmt { arg: None, .. })] => None,
+        [Stmt::Return(ReturnStmt { arg: Some(arg), .. })] => Some(arg.clone()),
+        _ => return None,
+    };
+    let mut used = HashSet::new();
+    if let Some(body) = &body {
+        let mut inspector = Inspector::default();
+        body.visit_with(&mut inspector);
+        // free variables must be the same at the call site
+        let shadowed = inspector
+            .idents
+            .iter()
+            .filter(|x| !params.contains(x))
+            .any(|x| names.nested.contains(x) || *x == f.ident.sym);
+        if inspector.forbidden || shadowed {
+            return None;
+        }
+        used.extend(inspector.idents.into_iter().filter(|x| params.contains(x)));
+    }
+    Some(Inlinable { params, used, body })
+}
+
+struct Inliner<'a> {
+    candidates: HashMap<JsWord, Inlinable>,
+    names: &'a Names,
+    inlined: HashSet<JsWord>,
+}
+
+impl VisitMut for Inliner<'_> {
+    fn visit_mut_expr(&mut self, n: &mut Expr) {
+        n.visit_mut_children_with(self);
+        let Expr::Call(CallExpr { callee: Callee::Expr(box Expr::Ident(callee)), args, .. }) = n
+        else {
+            return;
+        };
+        let Some(f) = self.candidates.get(&callee.sym) else { return };
+        // arguments are evaluated before the body, so they must not change in between.
+        // unused ones are dropped, so they must not throw either, as a missing global would.
+        let constant = args.iter().enumerate().all(|(i, x)| {
+            let used = f.params.get(i).map_or(false, |x| f.used.contains(x));
+            x.spread.is_none()
+                && match &*x.expr {
+                    Expr::Lit(Lit::Regex(_)) => false,
+                    Expr::Lit(_) => true,
+                    Expr::Ident(i) => {
+                        !self.names.assigned.contains(&i.sym)
+                            && (used || self.names.decls.contains_key(&i.sym))
+                    }
+                    _ => false,
+                }
+        });
+        if !constant {
+            return;
+        }
+
+        let name = callee.sym.clone();
+        let inlined = match &f.body {
+            Some(body) => {
+                let mut substitute = Substitute {
+                    params: f
+                        .params
+                        .iter()
+                        .enumerate()
+                        .map(|(i, param)| {
+                            let arg = args.get(i).map_or_else(undefined, |x| (*x.expr).clone());
+                            (param.clone(), arg)
+                        })
+                        .collect(),
+                };
+                let mut body = body.clone();
+                body.visit_mut_with(&mut substitute);
+                Expr::Paren(ParenExpr {
+                    span: DUMMY_SP,
+                    expr: body,
+                })
+            }
+            None => undefined(),
+        };
+        self.inlined.insert(name);
+        *n = inlined;
+    }
+}
+
+// `void 0`
+fn undefined() -> Expr {
+    Expr::Unary(UnaryExpr {
+        span: DUMMY_SP,
+        op: UnaryOp::Void,
+        arg: Box::new(Expr::Lit(Lit::Num(Number {
+            span: DUMMY_SP,
+            value: 0.0,
+            raw: None,
+        }))),
+    })
+}
+
+// replaces parameters with arguments.
+struct Substitute {
+    params: HashMap<JsWord, Expr>,
+}
+
+impl VisitMut for Substitute {
+    fn visit_mut_expr(&mut self, n: &mut Expr) {
+        if let Expr::Ident(i) = n
+            && let Some(arg) = self.params.get(&i.sym)
+        {
+            *n = arg.clone();
+            return;
+        }
+        n.visit_mut_children_with(self);
+    }
+}
+
+// finds what makes an expression unsafe to move into another function.
+#[derive(Default)]
+struct Inspector {
+    forbidden: bool,
+    /// referenced names
+    idents: Vec<JsWord>,
+}
+
+impl Visit for Inspector {
+    fn visit_ident(&mut self, n: &Ident) {
+        self.forbidden |= &*n.sym == "arguments";
+        self.idents.push(n.sym.clone());
+    }
+
+    fn visit_member_prop(&mut self, n: &MemberProp) {
+        if let MemberProp::Computed(c) = n {
+            c.visit_with(self);
+        }
+    }
+
+    fn visit_prop_name(&mut self, n: &PropName) {
+        if let PropName::Computed(c) = n {
+            c.visit_with(self);
+        }
+    }
+
+    // `{ x }` would need a key once `x` is substituted
+    fn visit_prop(&mut self, n: &Prop) {
+        self.forbidden |= matches!(n, Prop::Shorthand(_));
+        n.visit_children_with(self);
+    }
+
+    fn visit_this_expr(&mut self, _: &ThisExpr) {
+        self.forbidden = true;
+    }
+
+    fn visit_super(&mut self, _: &Super) {
+        self.forbidden = true;
+    }
+
+    fn visit_meta_prop_expr(&mut self, _: &MetaPropExpr) {
+        self.forbidden = true;
+    }
+
+    fn visit_function(&mut self, _: &Function) {
+        self.forbidden = true;
+    }
+
+    fn visit_arrow_expr(&mut self, _: &ArrowExpr) {
+        self.forbidden = true;
+    }
+
+    fn visit_class(&mut self, _: &Class) {
+        self.forbidden = true;
+    }
+
+    fn visit_assign_expr(&mut self, _: &AssignExpr) {
+        self.forbidden = true;
+    }
+
+    fn visit_update_expr(&mut self, _: &UpdateExpr) {
+        self.forbidden = true;
+    }
+
+    fn visit_yield_expr(&mut self, _: &YieldExpr) {
+        self.forbidden = true;
+    }
+
+    fn visit_await_expr(&mut self, _: &AwaitExpr) {
+        self.forbidden = true;
+    }
+}
+
+/// Drops fallbacks for environments without `TextDecoder`, `TextEncoder` or
+/// `TextEncoder.prototype.encodeInto`, which every supported browser has.
+pub struct TextCodec;
+
+// `typeof TextDecoder !== 'undefined'` or `typeof cachedTextEncoder.encodeInto === 'function'`
+fn is_codec_check(test: &Expr) -> bool {
+    let Expr::Bin(BinExpr { op, left, right, .. }) = test else { return false };
+    let (
+        Expr::Unary(UnaryExpr {
+            op: UnaryOp::TypeOf,
+            arg,
+            ..
+        }),
+        Expr::Lit(Lit::Str(Str { value, .. })),
+    ) = (&**left, &**right)
+    else {
+        return false;
+    };
+    match (op, &**value, &**arg) {
+        (BinaryOp::NotEqEq | BinaryOp::NotEq, "undefined", Expr::Ident(i)) => {
+            matches!(&*i.sym, "TextDecoder" | "TextEncoder")
+        }
+        (
+            BinaryOp::EqEqEq | BinaryOp::EqEq,
+            "function",
+            Expr::Member(MemberExpr {
+                prop: MemberProp::Ident(prop),
+                ..
+            }),
+        ) => &*prop.sym == "encodeInto",
+        _ => false,
+    }
+}
+
+impl VisitMut for TextCodec {
+    fn visit_mut_expr(&mut self, n: &mut Expr) {
+        n.visit_mut_children_with(self);
+        match n {
+            Expr::Cond(c) if is_codec_check(&c.test) => *n = (*c.cons).clone(),
+            // the label is ignored, `utf-8` is the only encoding
+            Expr::New(NewExpr {
+                callee: box Expr::Ident(i),
+                args: Some(args),
+                ..
+            }) if &*i.sym == "TextEncoder" => args.clear(),
+            _ => {}
+        }
+    }
+
+    fn visit_mut_stmt(&mut self, n: &mut Stmt) {
+        n.visit_mut_children_with(self);
+        if let Stmt::If(IfStmt {
+            test,
+            cons,
+            alt: None,
+            ..
+        }) = n
+            && is_codec_check(test)
+        {
+            *n = (**cons).clone();
+        }
+    }
+}
+
+/// Removes module level functions and variables never referenced, e.g. the heap helpers
+/// when no JS object is passed to wasm. Initializers must be side effect free.
+/// Setup calls such as `heap.push(undefined, null)` are removed along with their variable.
+pub struct UnusedHelpers;
+
+impl VisitMut for UnusedHelpers {
+    fn visit_mut_module(&mut self, m: &mut Module) {
+        // removing a helper may leave others unused
+        while remove_unused(m) {}
+    }
+}
+
+// `<name>.<method>(...);` with side effect free arguments
+fn setup_of<'a>(item: &'a ModuleItem, names: &Names, at: usize) -> Option<(&'a JsWord, Span)> {
+    let ModuleItem::Stmt(Stmt::Expr(ExprStmt {
+        expr: box Expr::Call(CallExpr {
+            callee: Callee::Expr(box Expr::Member(MemberExpr {
+                obj: box Expr::Ident(obj),
+                prop: MemberProp::Ident(_),
+                ..
+            })),
+            args,
+            ..
+        }),
+        span,
+    })) = item
+    else {
+        return None;
+    };
+    args.iter()
+        .all(|x| x.spread.is_none() && is_pure(&x.expr, names, at))
+        .then_some((&obj.sym, *span))
+}
+
+fn remove_unused(m: &mut Module) -> bool {
+    let names = Names::collect(m);
+    let mut setups = HashMap::<_, Vec<_>>::new();
+    for (at, item) in m.body.iter().enumerate() {
+        if let Some((name, span)) = setup_of(item, &names, at) {
+            setups.entry(name.clone()).or_default().push(span);
+        }
+    }
+    let unused = |name: &JsWord, decl: Span| {
+        let setups = setups.get(name).map_or(&[][..], |x| &x[..]);
+        names.decls.get(name) == Some(&1)
+            && names
+                .refs(name)
+                .iter()
+                .all(|x| contains(decl, *x) || setups.iter().any(|s| contains(*s, *x)))
+    };
+
+    let mut removed = HashSet::new();
+    for (at, item) in m.body.iter().enumerate() {
+        match item {
+            ModuleItem::Stmt(Stmt::Decl(Decl::Fn(f))) if unused(&f.ident.sym, f.function.span) => {
+                removed.insert(f.ident.sym.clone());
+            }
+            ModuleItem::Stmt(Stmt::Decl(Decl::Var(var))) => {
+                let all_unused = var.decls.iter().all(|x| {
+                    matches!(&x.name, Pat::Ident(i) if unused(&i.id.sym, var.span))
+                        && x.init.as_ref().map_or(true, |x| is_pure(x, &names, at))
+                });
+                if all_unused {
+                    removed.extend(var.decls.iter().filter_map(|x| match &x.name {
+                        Pat::Ident(i) => Some(i.id.sym.clone()),
+                        _ => None,
+                    }));
+                }
+            }
+            _ => {}
+        }
+    }
+    if removed.is_empty() {
+        return false;
+    }
+
+    m.body.retain(|item| match item {
+        ModuleItem::Stmt(Stmt::Decl(Decl::Fn(f))) => !removed.contains(&f.ident.sym),
+        ModuleItem::Stmt(Stmt::Decl(Decl::Var(var))) => !var
+            .decls
+            .iter()
+            .any(|x| matches!(&x.name, Pat::Ident(i) if removed.contains(&i.id.sym))),
+        ModuleItem::Stmt(Stmt::Expr(e)) => !removed
+            .iter()
+            .any(|x| setups.get(x).map_or(false, |x| x.contains(&e.span))),
+        _ => true,
+    });
+    true
+}
+
+fn local(n: &ImportSpecifier) -> &Ident {
+    match n {
+        ImportSpecifier::Named(x) => &x.local,
+        ImportSpecifier::Default(x) => &x.local,
+        ImportSpecifier::Namespace(x) => &x.local,
+    }
+}
+
+fn contains(outer: Span, inner: Span) -> bool {
+    outer.lo <= inner.lo && inner.hi <= outer.hi
+}
+
+// evaluated without running code of the module, at module item `at`. property reads
+// may run getters and most operators `valueOf`, so only those that can't are allowed,
+// besides a few builtins wasm-bindgen uses.
+fn is_pure(e: &Expr, names: &Names, at: usize) -> bool {
+    let is_pure = |e: &Expr| is_pure(e, names, at);
+    let all_pure =
+        |args: &[ExprOrSpread]| args.iter().all(|x| x.spread.is_none() && is_pure(&x.expr));
+    let global = |name: &JsWord| !names.decls.contains_key(name);
+    match e {
+        Expr::Lit(_) | Expr::Arrow(_) | Expr::Fn(_) => true,
+        // a variable declared below is not initialized yet once it is a `let`
+        Expr::Ident(i) => {
+            (&*i.sym == "undefined" && global(&i.sym)) || names.initialized_before(&i.sym, at)
+        }
+        // `typeof TextDecoder`, which doesn't throw for a missing global
+        Expr::Unary(UnaryExpr {
+            op: UnaryOp::TypeOf,
+            arg: box Expr::Ident(i),
+            ..
+        }) if global(&i.sym) => true,
+        Expr::Paren(x) => is_pure(&x.expr),
+        Expr::Unary(x) => {
+            matches!(x.op, UnaryOp::Bang | UnaryOp::TypeOf | UnaryOp::Void) && is_pure(&x.arg)
+        }
+        Expr::Bin(x) => {
+            matches!(
+                x.op,
+                BinaryOp::EqEqEq
+                    | BinaryOp::NotEqEq
+                    | BinaryOp::LogicalAnd
+                    | BinaryOp::LogicalOr
+                    | BinaryOp::NullishCoalescing
+            ) && is_pure(&x.left)
+                && is_pure(&x.right)
+        }
+        Expr::Cond(x) => is_pure(&x.test) && is_pure(&x.cons) && is_pure(&x.alt),
+        // interpolated objects would be converted by their `toString`
+        Expr::Tpl(x) => x
+            .exprs
+            .iter()
+            .all(|x| matches!(&**x, Expr::Lit(lit) if !matches!(lit, Lit::Regex(_)))),
+        Expr::Array(x) => x
+            .elems
+            .iter()
+            .flatten()
+            .all(|x| x.spread.is_none() && is_pure(&x.expr)),
+        Expr::Object(x) => x.props.iter().all(|x| match x {
+            PropOrSpread::Prop(box Prop::KeyValue(kv)) => {
+                !matches!(kv.key, PropName::Computed(_)) && is_pure(&kv.value)
+            }
+            PropOrSpread::Prop(box Prop::Shorthand(i)) => is_pure(&Expr::Ident(i.clone())),
+            PropOrSpread::Prop(box Prop::Method(_)) => true,
+            _ => false,
+        }),
+        // `heap.length`, an own data property of arrays
+        Expr::Member(MemberExpr {
+            obj: box Expr::Ident(obj),
+            prop: MemberProp::Ident(prop),
+            ..
+        }) => {
+            &*prop.sym == "length"
+                && names.arrays.contains(&obj.sym)
+                && names.initialized_before(&obj.sym, at)
+        }
+        // builtins wasm-bindgen constructs, not shadowed by the module
+        Expr::New(NewExpr {
+            callee: box Expr::Ident(callee),
+            args,
+            ..
+        }) => {
+            matches!(
+                &*callee.sym,
+                "TextDecoder" | "TextEncoder" | "Array" | "FinalizationRegistry"
+            ) && global(&callee.sym)
+                && all_pure(args.as_deref().unwrap_or(&[]))
+        }
+        // `new Array(128).fill(undefined)`
+        Expr::Call(CallExpr {
+            callee:
+                Callee::Expr(box Expr::Member(MemberExpr {
+                    obj: box obj @ Expr::New(_),
+                    prop: MemberProp::Ident(prop),
+                    ..
+                })),
+            args,
+            ..
+        }) if &*prop.sym == "fill" => is_pure(obj) && all_pure(args),
+        _ => false,
+    }
+}
+
+// `[...]`, `new Array(n)` or `new Array(n).fill(x)`.
+fn is_array(e: &Expr) -> bool {
+    match e {
+        Expr::Array(_) => true,
+        Expr::New(NewExpr {
+            callee: box Expr::Ident(callee),
+            ..
+        }) => &*callee.sym == "Array",
+        Expr::Call(CallExpr {
+            callee:
+                Callee::Expr(box Expr::Member(MemberExpr {
+                    obj: box obj,
+                    prop: MemberProp::Ident(prop),
+                    ..
+                })),
+            ..
+        }) => &*prop.sym == "fill" && is_array(obj),
+        _ => false,
+    }
+}
+
+// module level code up to which nothing has run yet.
+fn is_inert(item: &ModuleItem, names: &Names, at: usize) -> bool {
+    match item {
+        ModuleItem::ModuleDecl(
+            ModuleDecl::Import(_)
+            | ModuleDecl::ExportDecl(ExportDecl {
+                decl: Decl::Fn(_), ..
+            }),
+        ) => true,
+        ModuleItem::Stmt(Stmt::Decl(Decl::Fn(_)) | Stmt::Empty(_)) => true,
+        ModuleItem::Stmt(Stmt::Decl(Decl::Var(var))) => var
+            .decls
+            .iter()
+            .all(|x| x.init.as_ref().map_or(true, |x| is_pure(x, names, at))),
+        _ => false,
+    }
+}
+
+// where each name is declared and referenced.
+#[derive(Default)]
+struct Names {
+    /// spans of references, declarations excluded
+    refs: HashMap<JsWord, Vec<Span>>,
+    /// number of declarations at any depth
+    decls: HashMap<JsWord, usize>,
+    /// declared in a function, class or block
+    nested: HashSet<JsWord>,
+    /// assigned or updated besides the declaration
+    assigned: HashSet<JsWord>,
+    /// module level names => index of the first module item running after their
+    /// initialization. 0 for imports and functions.
+    initialized: HashMap<JsWord, usize>,
+    /// module level names initialized with an array and never assigned
+    arrays: HashSet<JsWord>,
+    depth: usize,
+    // inside the left side of `[a, b] = ...`
+    assigning: bool,
+}
+
+impl Names {
+    fn collect(m: &Module) -> Self {
+        let mut names = Names::default();
+        m.visit_with(&mut names);
+        for (i, item) in m.body.iter().enumerate() {
+            let decl = match item {
+                ModuleItem::Stmt(Stmt::Decl(decl))
+                | ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(ExportDecl { decl, .. })) => decl,
+                ModuleItem::ModuleDecl(ModuleDecl::Import(import)) => {
+                    for x in &import.specifiers {
+                        names.initialized.insert(local(x).sym.clone(), 0);
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            match decl {
+                Decl::Fn(f) => {
+                    names.initialized.insert(f.ident.sym.clone(), 0);
+                }
+                Decl::Class(c) => {
+                    names.initialized.insert(c.ident.sym.clone(), i + 1);
+                }
+                Decl::Var(var) => {
+                    for x in &var.decls {
+                        if let Pat::Ident(id) = &x.name {
+                            names.initialized.insert(id.id.sym.clone(), i + 1);
+                            if x.init.as_deref().map_or(false, is_array)
+                                && !names.assigned.contains(&id.id.sym)
+                            {
+                                names.arrays.insert(id.id.sym.clone());
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        names
+    }
+
+    fn initialized_before(&self, name: &JsWord, at: usize) -> bool {
+        self.decls.get(name) == Some(&1) && self.initialized.get(name).map_or(false, |x| *x <= at)
+    }
+
+    fn refs(&self, name: &JsWord) -> &[Span] {
+        self.refs.get(name).map_or(&[][..], |x| &x[..])
+    }
+
+    fn declare(&mut self, id: &Ident) {
+        *self.decls.entry(id.sym.clone()).or_default() += 1;
+        if self.depth > 0 {
+            self.nested.insert(id.sym.clone());
+        }
+    }
+
+    fn nested<T: VisitWith<Self>>(&mut self, n: &T) {
+        let assigning = std::mem::take(&mut self.assigning);
+        self.depth += 1;
+        n.visit_children_with(self);
+        self.depth -= 1;
+        self.assigning = assigning;
+    }
+}
+
+impl Visit for Names {
+    fn visit_ident(&mut self, n: &Ident) {
+        self.refs.entry(n.sym.clone()).or_default().push(n.span);
+    }
+
+    fn visit_binding_ident(&mut self, n: &BindingIdent) {
+        if self.assigning {
+            self.assigned.insert(n.id.sym.clone());
+            self.visit_ident(&n.id);
+        } else {
+            self.declare(&n.id);
+        }
+    }
+
+    // `key` of `{ key = value } = ...` or `const { key } = ...`
+    fn visit_assign_pat_prop(&mut self, n: &AssignPatProp) {
+        if self.assigning {
+            self.assigned.insert(n.key.sym.clone());
+            self.visit_ident(&n.key);
+        } else {
+            self.declare(&n.key);
+        }
+        n.value.visit_with(self);
+    }
+
+    fn visit_fn_decl(&mut self, n: &FnDecl) {
+        self.declare(&n.ident);
+        n.function.visit_with(self);
+    }
+
+    fn visit_class_decl(&mut self, n: &ClassDecl) {
+        self.declare(&n.ident);
+        n.class.visit_with(self);
+    }
+
+    // the name of a function expression is visible only inside
+    fn visit_fn_expr(&mut self, n: &FnExpr) {
+        if let Some(ident) = &n.ident {
+            self.depth += 1;
+            self.declare(ident);
+            self.depth -= 1;
+        }
+        n.function.visit_with(self);
+    }
+
+    fn visit_class_expr(&mut self, n: &ClassExpr) {
+        if let Some(ident) = &n.ident {
+            self.depth += 1;
+            self.declare(ident);
+            self.depth -= 1;
+        }
+        n.class.visit_with(self);
+    }
+
+    fn visit_import_specifier(&mut self, n: &ImportSpecifier) {
+        self.declare(local(n));
+    }
+
+    fn visit_assign_expr(&mut self, n: &AssignExpr) {
+        match &n.left {
+            PatOrExpr::Expr(box Expr::Ident(i))
+            | PatOrExpr::Pat(box Pat::Expr(box Expr::Ident(i))) => {
+                self.assigned.insert(i.sym.clone());
+                self.visit_ident(i);
+            }
+            PatOrExpr::Pat(pat) => {
+                let assigning = std::mem::replace(&mut self.assigning, true);
+                pat.visit_with(self);
+                self.assigning = assigning;
+            }
+            PatOrExpr::Expr(e) => e.visit_with(self),
+        }
+        n.right.visit_with(self);
+    }
+
+    fn visit_update_expr(&mut self, n: &UpdateExpr) {
+        if let Expr::Ident(i) = &*n.arg {
+            self.assigned.insert(i.sym.clone());
+        }
+        n.visit_children_with(self);
+    }
+
+    fn visit_member_prop(&mut self, n: &MemberProp) {
+        if let MemberProp::Computed(c) = n {
+            c.visit_with(self);
+        }
+    }
+
+    fn visit_prop_name(&mut self, n: &PropName) {
+        if let PropName::Computed(c) = n {
+            c.visit_with(self);
+        }
+    }
+
+    fn visit_function(&mut self, n: &Function) {
+        self.nested(n);
+    }
+
+    fn visit_arrow_expr(&mut self, n: &ArrowExpr) {
+        self.nested(n);
+    }
+
+    fn visit_class(&mut self, n: &Class) {
+        self.nested(n);
+    }
+
+    fn visit_block_stmt(&mut self, n: &BlockStmt) {
+        self.nested(n);
+    }
+
+    fn visit_catch_clause(&mut self, n: &CatchClause) {
+        self.nested(n);
+    }
+
+    fn visit_for_stmt(&mut self, n: &ForStmt) {
+        self.nested(n);
+    }
+
+    fn visit_for_in_stmt(&mut self, n: &ForInStmt) {
+        self.nested(n);
+    }
+
+    fn visit_for_of_stmt(&mut self, n: &ForOfStmt) {
+        self.nested(n);
+    }
+}
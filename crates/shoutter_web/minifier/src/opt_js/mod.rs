@@ -0,0 +1,895 @@
+use std::collections::{HashMap, HashSet};
+
+mod arrow;
+#[cfg(test)]
+mod differential;
+mod glue;
+
+use anyhow::{bail, ensure, Result};
+use swc_core::common::input::StringInput;
+use swc_core::common::sync::Lrc;
+use swc_core::common::{BytePos, FileName, LineCol, Mark, SourceMap, DUMMY_SP, GLOBALS};
+use swc_core::ecma::ast::{
+    ArrowExpr, AssignExpr, AssignOp, BinExpr, BinaryOp, BindingIdent, BlockStmt, BlockStmtOrExpr,
+    Callee, Decl, EsVersion, Expr, ExprStmt, Ident, Lit, MemberExpr, MemberProp, Module,
+    ModuleDecl, ModuleItem, NamedExport, Pat, PatOrExpr, Program, ReturnStmt, Stmt, Str, VarDecl,
+    VarDeclKind, VarDeclarator,
+};
+use swc_core::ecma::atoms::JsWord;
+use swc_core::ecma::codegen::text_writer::JsWriter;
+use swc_core::ecma::codegen::Emitter;
+use swc_core::ecma::minifier::optimize;
+use swc_core::ecma::minifier::option::terser::TerserCompressorOptions;
+use swc_core::ecma::minifier::option::{ExtraOptions, MangleOptions, MinifyOptions};
+use swc_core::ecma::parser::lexer::Lexer;
+use swc_core::ecma::parser::Parser;
+use swc_core::ecma::transforms::base::fixer::fixer;
+use swc_core::ecma::transforms::base::resolver;
+use swc_core::ecma::visit::{FoldWith, Visit, VisitMut, VisitMutWith, VisitWith};
+
+use self::arrow::FunctionToArrowFn;
+use self::glue::{InlineFunctions, TextCodec, UnusedHelpers, VarToLet};
+use crate::error::{LocatedError, SourceLocation};
+use crate::symbol::{SymbolMap, SymbolUsage};
+
+/// Passes for wasm-bindgen glue (see `glue`). `Default` enables none of them, while the
+/// config runs every pass not skipped (see [`JsOptions::skipping`]).
+#[derive(Debug, Default, Clone)]
+pub struct JsOptions {
+    /// `var-to-let`: module level `var` to `let` or `const`.
+    pub var_to_let: bool,
+    /// `inline-functions`: inline functions called once.
+    pub inline_functions: bool,
+    /// `text-codec`: drop fallbacks for missing `TextDecoder` and `TextEncoder`.
+    pub text_codec: bool,
+    /// `unused-helpers`: remove functions and variables never referenced.
+    pub unused_helpers: bool,
+    /// `intern-strings`: move repeated string literals into `const`s where it pays off.
+    pub intern_strings: bool,
+    /// compress and mangle after the passes above, in place of terser. Not a glue pass:
+    /// set for every javascript file when swc is the javascript minifier.
+    pub minify: bool,
+}
+
+impl JsOptions {
+    /// Every pass but `skipped`, given by the names above.
+    pub fn skipping(skipped: &[String]) -> Result<Self> {
+        let mut options = JsOptions {
+            var_to_let: true,
+            inline_functions: true,
+            text_codec: true,
+            unused_helpers: true,
+            intern_strings: true,
+            minify: false,
+        };
+        for pass in skipped {
+            match pass.as_str() {
+                "var-to-let" => options.var_to_let = false,
+                "inline-functions" => options.inline_functions = false,
+                "text-codec" => options.text_codec = false,
+                "unused-helpers" => options.unused_helpers = false,
+                "intern-strings" => options.intern_strings = false,
+                _ => bail!("unknown js pass `{pass}`"),
+            }
+        }
+        Ok(options)
+    }
+}
+
+pub fn optimize_js(
+    js: impl Into<String>,
+    symbols: Option<&SymbolMap>,
+    options: &JsOptions,
+) -> Result<String> {
+    let cm: Lrc<SourceMap> = Default::default();
+    let module = transform(&cm, "in.js", js.into(), symbols, options)?;
+    emit(cm, &module, None, options.minify)
+}
+
+/// Same as [`optimize_js`] but also generates a source map.
+pub fn optimize_js_with_source_map(
+    js: impl Into<String>,
+    file_name: &str,
+    symbols: Option<&SymbolMap>,
+    options: &JsOptions,
+) -> Result<(String, sourcemap::SourceMap)> {
+    let cm: Lrc<SourceMap> = Default::default();
+    let module = transform(&cm, file_name, js.into(), symbols, options)?;
+    let mut mappings = vec![];
+    let code = emit(cm.clone(), &module, Some(&mut mappings), options.minify)?;
+    let source_map = cm.build_source_map(&mut mappings);
+    Ok((code, source_map))
+}
+
+/// Compresses and mangles an inline `<script>` as `optimize-js` does with `minify`.
+/// Classic scripts share their top level with other scripts, so names there are kept.
+pub fn minify_script(js: &str, module: bool) -> Result<String> {
+    let cm: Lrc<SourceMap> = Default::default();
+    let fm = cm.new_source_file(FileName::Anon, js.to_owned());
+    let mut parser = Parser::new_from(Lexer::new(
+        Default::default(),
+        EsVersion::latest(),
+        StringInput::from(&*fm),
+        None,
+    ));
+    let program = if module {
+        parser.parse_module().map(Program::Module)
+    } else {
+        parser.parse_script().map(Program::Script)
+    }
+    .map_err(|e| located(&cm, e))?;
+    let program = minify(&cm, program, module)?;
+
+    let mut buf = vec![];
+    let mut emitter = Emitter {
+        cfg: swc_core::ecma::codegen::Config {
+            minify: true,
+            ..Default::default()
+        },
+        cm: cm.clone(),
+        comments: Default::default(),
+        wr: Box::new(JsWriter::new(cm, "\n", &mut buf, None)),
+    };
+    match &program {
+        Program::Module(module) => emitter.emit_module(module)?,
+        Program::Script(script) => emitter.emit_script(script)?,
+    }
+    drop(emitter);
+    Ok(String::from_utf8(buf)?)
+}
+
+/// Counts references to the wasm module from wasm-bindgen glue.
+pub fn count_wasm_symbols(js: &str) -> Result<SymbolUsage> {
+    let cm: Lrc<SourceMap> = Default::default();
+    let module = parse(&cm, "in.js", js.to_owned())?;
+    let mut counter = CountWasmSymbols::default();
+    module.visit_with(&mut counter);
+    Ok(counter.finish())
+}
+
+fn parse(cm: &Lrc<SourceMap>, file_name: &str, js: String) -> Result<Module> {
+    let fm = cm.new_source_file(FileName::Real(file_name.into()), js);
+    let module = Parser::new_from(Lexer::new(
+        Default::default(),
+        EsVersion::latest(),
+        StringInput::from(&*fm),
+        None,
+    ))
+    .parse_module()
+    .map_err(|e| located(cm, e))?;
+    Ok(module)
+}
+
+fn located(cm: &SourceMap, e: swc_core::ecma::parser::error::Error) -> LocatedError {
+    let loc = cm.lookup_char_pos(e.span().lo);
+    LocatedError {
+        location: SourceLocation {
+            line: loc.line,
+            column: loc.col.0,
+        },
+        message: e.into_kind().msg().into_owned(),
+    }
+}
+
+// `symbols` is given for wasm-bindgen glue whose wasm symbols were minified.
+fn transform(
+    cm: &Lrc<SourceMap>,
+    file_name: &str,
+    js: String,
+    symbols: Option<&SymbolMap>,
+    options: &JsOptions,
+) -> Result<Module> {
+    let module = parse(cm, file_name, js)?;
+
+    let mut program = Program::Module(module);
+    if let Some(symbols) = symbols {
+        program.visit_mut_with(&mut RemoveUnusedImports::new(&symbols.unused_imports));
+        let mut renamer = RenameWasmSymbols::new(symbols);
+        program.visit_mut_with(&mut renamer);
+        renamer.finish()?;
+    }
+    // fallbacks dropped by `TextCodec` leave helpers unused
+    if options.text_codec {
+        program.visit_mut_with(&mut TextCodec);
+    }
+    if options.inline_functions {
+        program.visit_mut_with(&mut InlineFunctions);
+    }
+    if options.unused_helpers {
+        program.visit_mut_with(&mut UnusedHelpers);
+    }
+    if options.var_to_let {
+        program.visit_mut_with(&mut VarToLet);
+    }
+
+    let mut module = program.expect_module();
+    let mut arrows = FunctionToArrowFn::new(&module);
+    for (span, name, skip) in arrows.skipped() {
+        let loc = cm.lookup_char_pos(span.lo);
+        let name = name.map_or("<anonymous>", |x| &**x);
+        tracing::debug!(
+            "kept function {name} at {file_name}:{}:{}: {skip}",
+            loc.line,
+            loc.col.0 + 1
+        );
+    }
+    module.visit_mut_with(&mut arrows);
+    if options.intern_strings {
+        module.visit_mut_with(&mut InternString);
+    }
+    if options.minify {
+        module = minify(cm, Program::Module(module), true)?.expect_module();
+    }
+    // e.g. an object literal moved into the body of an arrow function
+    Ok(module.fold_with(&mut fixer(None)))
+}
+
+// what terser does with `js_minifier_option` in `sys::minifier`, on the program at hand
+// instead of its printed text. spans survive, so source maps point at the input.
+// `toplevel` drops and renames top level names, which only a module owns.
+fn minify(cm: &Lrc<SourceMap>, program: Program, toplevel: bool) -> Result<Program> {
+    let compress: TerserCompressorOptions = serde_json::from_value(serde_json::json!({
+        "ecma": 2021,
+        "passes": 3,
+        "pure_getters": true,
+        "toplevel": toplevel,
+    }))?;
+    let mangle: MangleOptions =
+        serde_json::from_value(serde_json::json!({ "topLevel": toplevel }))?;
+    let options = MinifyOptions {
+        compress: Some(compress.into_config(cm.clone())),
+        mangle: Some(mangle),
+        ..Default::default()
+    };
+    GLOBALS.set(&Default::default(), || {
+        let unresolved_mark = Mark::new();
+        let top_level_mark = Mark::new();
+        let program = program.fold_with(&mut resolver(unresolved_mark, top_level_mark, false));
+        let program = optimize(
+            program,
+            cm.clone(),
+            None,
+            None,
+            &options,
+            &ExtraOptions {
+                unresolved_mark,
+                top_level_mark,
+            },
+        );
+        Ok(program.fold_with(&mut fixer(None)))
+    })
+}
+
+fn emit(
+    cm: Lrc<SourceMap>,
+    module: &Module,
+    mappings: Option<&mut Vec<(BytePos, LineCol)>>,
+    minify: bool,
+) -> Result<String> {
+    let mut buf = vec![];
+    Emitter {
+        cfg: swc_core::ecma::codegen::Config {
+            minify,
+            ..Default::default()
+        },
+        cm: cm.clone(),
+        comments: Default::default(),
+        wr: Box::new(JsWriter::new(cm, "\n", &mut buf, mappings)),
+    }
+    .emit_module(module)?;
+    Ok(String::from_utf8(buf)?)
+}
+
+/// Renames wasm imports and exports referenced from wasm-bindgen glue:
+/// `imports.<module>`, `imports.<module>.<name>` and `wasm.<export>`.
+pub struct RenameWasmSymbols<'a> {
+    symbols: &'a SymbolMap,
+    referenced_modules: HashSet<String>,
+    referenced_imports: HashSet<(String, String)>,
+    unmatched: Vec<String>,
+}
+
+impl<'a> RenameWasmSymbols<'a> {
+    fn new(symbols: &'a SymbolMap) -> Self {
+        Self {
+            symbols,
+            referenced_modules: HashSet::new(),
+            referenced_imports: HashSet::new(),
+            unmatched: vec![],
+        }
+    }
+
+    // an import left unreferenced means the glue and the module no longer agree,
+    // which would fail at instantiation. unreferenced exports are harmless.
+    fn finish(self) -> Result<()> {
+        let mut errors = self.unmatched;
+        for (module, (_, names)) in &self.symbols.imports {
+            if !self.referenced_modules.contains(module) {
+                errors.push(format!("`imports.{module}` is never referenced"));
+            }
+            for name in names.keys() {
+                let import = (module.clone(), name.clone());
+                // shims of unused imports are removed but one (see `RemoveUnusedImports`)
+                if !self.referenced_imports.contains(&import)
+                    && !self.symbols.unused_imports.contains(&import)
+                {
+                    errors.push(format!("`imports.{module}.{name}` is never referenced"));
+                }
+            }
+        }
+        errors.sort();
+        ensure!(
+            errors.is_empty(),
+            "glue does not match the wasm module: {}",
+            errors.join(", "),
+        );
+        Ok(())
+    }
+}
+
+impl VisitMut for RenameWasmSymbols<'_> {
+    // outer expression first so that `imports.<module>` is still the original one
+    // when `imports.<module>.<name>` is looked up.
+    fn visit_mut_member_expr(&mut self, n: &mut MemberExpr) {
+        if let MemberProp::Ident(prop) = &mut n.prop {
+            match &*n.obj {
+                Expr::Ident(obj) if &*obj.sym == "wasm" => {
+                    match self.symbols.exports.get(&*prop.sym) {
+                        Some(renamed) => prop.sym = JsWord::from(renamed.as_str()),
+                        None => self
+                            .unmatched
+                            .push(format!("`wasm.{}` is not exported", prop.sym)),
+                    }
+                }
+                Expr::Ident(obj) if &*obj.sym == "imports" => {
+                    match self.symbols.imports.get(&*prop.sym) {
+                        Some((renamed, _)) => {
+                            self.referenced_modules.insert(prop.sym.to_string());
+                            prop.sym = JsWord::from(renamed.as_str());
+                        }
+                        None => self
+                            .unmatched
+                            .push(format!("`imports.{}` is not imported", prop.sym)),
+                    }
+                }
+                Expr::Member(MemberExpr {
+                    obj: box Expr::Ident(obj),
+                    prop: MemberProp::Ident(module),
+                    ..
+                }) if &*obj.sym == "imports" => {
+                    let renamed = self
+                        .symbols
+                        .imports
+                        .get(&*module.sym)
+                        .and_then(|(_, names)| names.get(&*prop.sym));
+                    match renamed {
+                        Some(renamed) => {
+                            self.referenced_imports
+                                .insert((module.sym.to_string(), prop.sym.to_string()));
+                            prop.sym = JsWord::from(renamed.as_str());
+                        }
+                        None => self.unmatched.push(format!(
+                            "`imports.{}.{}` is not imported",
+                            module.sym, prop.sym
+                        )),
+                    }
+                }
+                _ => {}
+            }
+        }
+        n.visit_mut_children_with(self);
+    }
+}
+
+// besides counting, finds whether `wasm` is used other than `wasm.<export>`.
+// `wasm = ...`, `let wasm` and `wasm === undefined` are not counted as such. `return wasm`
+// (wasm-bindgen's `init`) hands every export to callers, which is told apart in `returned`.
+#[derive(Default)]
+pub struct CountWasmSymbols {
+    usage: SymbolUsage,
+    wasm_idents: usize,
+    wasm_known_uses: usize,
+}
+
+impl CountWasmSymbols {
+    fn finish(mut self) -> SymbolUsage {
+        self.usage.escaped = self.wasm_idents > self.wasm_known_uses;
+        self.usage
+    }
+}
+
+fn is_wasm(e: &Expr) -> bool {
+    matches!(e, Expr::Ident(i) if &*i.sym == "wasm")
+}
+
+impl Visit for CountWasmSymbols {
+    fn visit_ident(&mut self, n: &Ident) {
+        if &*n.sym == "wasm" {
+            self.wasm_idents += 1;
+        }
+    }
+
+    fn visit_var_declarator(&mut self, n: &VarDeclarator) {
+        if let Pat::Ident(i) = &n.name
+            && &*i.id.sym == "wasm"
+        {
+            self.wasm_known_uses += 1;
+        }
+        n.visit_children_with(self);
+    }
+
+    fn visit_assign_expr(&mut self, n: &AssignExpr) {
+        match &n.left {
+            PatOrExpr::Pat(box Pat::Ident(i)) if &*i.id.sym == "wasm" => self.wasm_known_uses += 1,
+            PatOrExpr::Expr(e) | PatOrExpr::Pat(box Pat::Expr(e)) if is_wasm(e) => {
+                self.wasm_known_uses += 1
+            }
+            _ => {}
+        }
+        n.visit_children_with(self);
+    }
+
+    fn visit_return_stmt(&mut self, n: &ReturnStmt) {
+        if let Some(arg) = &n.arg
+            && is_wasm(arg)
+        {
+            self.wasm_known_uses += 1;
+            self.usage.returned = true;
+        }
+        n.visit_children_with(self);
+    }
+
+    fn visit_bin_expr(&mut self, n: &BinExpr) {
+        let is_undefined = |e: &Expr| matches!(e, Expr::Ident(i) if &*i.sym == "undefined");
+        let compares = matches!(
+            n.op,
+            BinaryOp::EqEq | BinaryOp::NotEq | BinaryOp::EqEqEq | BinaryOp::NotEqEq
+        );
+        if compares
+            && (is_wasm(&n.left) && is_undefined(&n.right)
+                || is_undefined(&n.left) && is_wasm(&n.right))
+        {
+            self.wasm_known_uses += 1;
+        }
+        n.visit_children_with(self);
+    }
+
+    fn visit_member_expr(&mut self, n: &MemberExpr) {
+        if let MemberProp::Ident(prop) = &n.prop {
+            let prop = prop.sym.to_string();
+            match &*n.obj {
+                Expr::Ident(obj) if &*obj.sym == "wasm" => {
+                    self.wasm_known_uses += 1;
+                    *self.usage.exports.entry(prop).or_insert(0) += 1;
+                }
+                Expr::Ident(obj) if &*obj.sym == "imports" => {
+                    *self.usage.modules.entry(prop).or_insert(0) += 1;
+                }
+                Expr::Member(MemberExpr {
+                    obj: box Expr::Ident(obj),
+                    prop: MemberProp::Ident(module),
+                    ..
+                }) if &*obj.sym == "imports" => {
+                    *self
+                        .usage
+                        .imports
+                        .entry((module.sym.to_string(), prop))
+                        .or_insert(0) += 1;
+                }
+                _ => {}
+            }
+        }
+        n.visit_children_with(self);
+    }
+}
+
+/// Removes shims of imports never called from wasm.
+/// One is left per module, as a no-op, for the name shared by all of them.
+pub struct RemoveUnusedImports<'a> {
+    unused: &'a HashSet<(String, String)>,
+    defined_modules: HashSet<String>,
+}
+
+impl<'a> RemoveUnusedImports<'a> {
+    fn new(unused: &'a HashSet<(String, String)>) -> Self {
+        Self {
+            unused,
+            defined_modules: HashSet::new(),
+        }
+    }
+
+    // `imports.<module>.<name> = ...;`
+    fn shim(stmt: &mut Stmt) -> Option<(&mut AssignExpr, (String, String))> {
+        let Stmt::Expr(ExprStmt { expr: box Expr::Assign(assign), .. }) = stmt else {
+            return None;
+        };
+        let (PatOrExpr::Expr(box Expr::Member(member))
+        | PatOrExpr::Pat(box Pat::Expr(box Expr::Member(member)))) = &assign.left
+        else {
+            return None;
+        };
+        let MemberExpr {
+            obj: box Expr::Member(MemberExpr {
+                obj: box Expr::Ident(obj),
+                prop: MemberProp::Ident(module),
+                ..
+            }),
+            prop: MemberProp::Ident(name),
+            ..
+        } = member
+        else {
+            return None;
+        };
+        if assign.op != AssignOp::Assign || &*obj.sym != "imports" {
+            return None;
+        }
+        let import = (module.sym.to_string(), name.sym.to_string());
+        Some((assign, import))
+    }
+
+    fn keep(&mut self, stmt: &mut Stmt) -> bool {
+        let Some((assign, import)) = Self::shim(stmt) else { return true };
+        if !self.unused.contains(&import) {
+            return true;
+        }
+        if !self.defined_modules.insert(import.0) {
+            return false;
+        }
+        assign.right = Box::new(Expr::Arrow(ArrowExpr {
+            span: DUMMY_SP,
+            params: vec![],
+            body: Box::new(BlockStmtOrExpr::BlockStmt(BlockStmt {
+                span: DUMMY_SP,
+                stmts: vec![],
+            })),
+            is_async: false,
+            is_generator: false,
+            type_params: None,
+            return_type: None,
+        }));
+        true
+    }
+}
+
+impl VisitMut for RemoveUnusedImports<'_> {
+    fn visit_mut_module_items(&mut self, n: &mut Vec<ModuleItem>) {
+        n.visit_mut_children_with(self);
+        n.retain_mut(|item| match item {
+            ModuleItem::Stmt(stmt) => self.keep(stmt),
+            ModuleItem::ModuleDecl(_) => true,
+        });
+    }
+
+    fn visit_mut_stmts(&mut self, n: &mut Vec<Stmt>) {
+        n.visit_mut_children_with(self);
+        n.retain_mut(|stmt| self.keep(stmt));
+    }
+}
+
+/// Moves string literals into `const`s at the top of the module where
+/// [`interning_saves`] estimates a saving. Modules importing others are left as they are, since those
+/// may call back into this one before the `const`s are initialized.
+pub struct InternString;
+
+// estimated bytes after terser and brotli. terser mangles the name down to a letter or two,
+// and brotli turns repeats of a literal into back references of a few bytes.
+// `bench/intern_strings.mjs` on `corpus/glue.js` saw 16 uses of a short literal save 0 to 5
+// bytes and 8 uses -1 to 3, which these fit. terser may still inline the `const`s back.
+const BACK_REFERENCE_COST: f64 = 2.0;
+const NAME_COST: f64 = 1.5;
+// `a="...",` besides the literal itself
+const DECLARATION_COST: f64 = 4.0;
+
+/// Estimated bytes saved by interning a string of `len` bytes used `count` times.
+fn interning_saves(len: usize, count: usize) -> f64 {
+    let literal = len as f64 + 2.0;
+    let count = count as f64;
+    // the first copy is there either way
+    let repeated = (count - 1.0) * literal.min(BACK_REFERENCE_COST);
+    repeated - DECLARATION_COST - count * NAME_COST
+}
+
+impl VisitMut for InternString {
+    fn visit_mut_module(&mut self, m: &mut Module) {
+        let mut counter = CountStringLiteral::default();
+        m.visit_with(&mut counter);
+        if counter.imports {
+            return;
+        }
+
+        let mut interned = counter
+            .count
+            .into_iter()
+            .filter(|(lit, count)| interning_saves(lit.len(), *count) > 0.0)
+            .collect::<Vec<_>>();
+        if interned.is_empty() {
+            return;
+        }
+        interned.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        // names used nowhere in the module, so never shadowed
+        let mut names = (0..).map(|i| JsWord::from(format!("__minifier_str{i}")));
+        let interned = interned
+            .into_iter()
+            .map(|(lit, _)| {
+                let name = names.find(|x| !counter.idents.contains(x)).unwrap();
+                (lit, name)
+            })
+            .collect::<Vec<_>>();
+        let lookup = interned.iter().cloned().collect::<HashMap<_, _>>();
+        let mut replacer = ReplaceStringLiteral::new(|lit| lookup.get(lit).cloned());
+        m.visit_mut_children_with(&mut replacer);
+
+        // directives such as `'use strict'` only count at the very top
+        let directives = m
+            .body
+            .iter()
+            .take_while(|x| {
+                matches!(
+                    x,
+                    ModuleItem::Stmt(Stmt::Expr(ExprStmt {
+                        expr: box Expr::Lit(Lit::Str(_)),
+                        ..
+                    }))
+                )
+            })
+            .count();
+        m.body.insert(
+            directives,
+            ModuleItem::Stmt(Stmt::Decl(Decl::Var(Box::new(VarDecl {
+                span: DUMMY_SP,
+                kind: VarDeclKind::Const,
+                declare: false,
+                decls: interned
+                    .into_iter()
+                    .map(|(lit, name)| VarDeclarator {
+                        span: DUMMY_SP,
+                        name: Pat::Ident(BindingIdent {
+                            id: Ident::new(name, DUMMY_SP),
+                            type_ann: None,
+                        }),
+                        init: Some(Box::new(Expr::Lit(Lit::Str(Str {
+                            span: DUMMY_SP,
+                            value: lit,
+                            raw: None,
+                        })))),
+                        definite: false,
+                    })
+                    .collect(),
+            })))),
+        );
+    }
+}
+
+// string literals in expressions, besides directives such as `'use strict'`.
+#[derive(Default)]
+pub struct CountStringLiteral {
+    count: HashMap<JsWord, usize>,
+    idents: HashSet<JsWord>,
+    imports: bool,
+}
+
+impl Visit for CountStringLiteral {
+    fn visit_expr(&mut self, n: &Expr) {
+        match n {
+            Expr::Lit(Lit::Str(s)) => *self.count.entry(s.value.clone()).or_insert(0) += 1,
+            _ => n.visit_children_with(self),
+        }
+    }
+
+    fn visit_expr_stmt(&mut self, n: &ExprStmt) {
+        if !matches!(&*n.expr, Expr::Lit(Lit::Str(_))) {
+            n.visit_children_with(self);
+        }
+    }
+
+    fn visit_ident(&mut self, n: &Ident) {
+        self.idents.insert(n.sym.clone());
+    }
+
+    fn visit_module_decl(&mut self, n: &ModuleDecl) {
+        self.imports |= matches!(
+            n,
+            ModuleDecl::Import(_)
+                | ModuleDecl::ExportAll(_)
+                | ModuleDecl::ExportNamed(NamedExport { src: Some(_), .. })
+        );
+        n.visit_children_with(self);
+    }
+
+    fn visit_callee(&mut self, n: &Callee) {
+        self.imports |= matches!(n, Callee::Import(_));
+        n.visit_children_with(self);
+    }
+}
+
+pub struct ReplaceStringLiteral<F> {
+    should_replace: F,
+}
+impl<F> ReplaceStringLiteral<F>
+where
+    F: FnMut(&JsWord) -> Option<JsWord>,
+{
+    fn new(should_replace: F) -> Self {
+        Self { should_replace }
+    }
+}
+
+impl<F> VisitMut for ReplaceStringLiteral<F>
+where
+    F: FnMut(&JsWord) -> Option<JsWord>,
+{
+    fn visit_mut_expr(&mut self, expr: &mut Expr) {
+        let Expr::Lit(Lit::Str(lit)) = expr else {
+            expr.visit_mut_children_with(self);
+            return;
+        };
+
+        let Some(rep_ident) = (self.should_replace)(&lit.value) else { return };
+        *expr = Expr::Ident(Ident {
+            span: DUMMY_SP,
+            sym: rep_ident,
+            optional: false,
+        });
+    }
+
+    fn visit_mut_expr_stmt(&mut self, n: &mut ExprStmt) {
+        if !matches!(&*n.expr, Expr::Lit(Lit::Str(_))) {
+            n.visit_mut_children_with(self);
+        }
+    }
+}
+
+#[test]
+fn rename_wasm_symbols() {
+    let symbols = SymbolMap {
+        imports: HashMap::from([(
+            "wbg".to_owned(),
+            (
+                "a".to_owned(),
+                HashMap::from([("__wbg_log_1234".to_owned(), "b".to_owned())]),
+            ),
+        )]),
+        exports: HashMap::from([("greet".to_owned(), "c".to_owned())]),
+        unused_imports: HashSet::new(),
+    };
+    let glue = r#"
+        const imports = {};
+        imports.wbg = {};
+        imports.wbg.__wbg_log_1234 = (a) => console.log(a);
+        export const greet = () => wasm.greet(wasm.greeting);
+    "#;
+
+    let err = optimize_js(glue, Some(&symbols), &Default::default())
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("`wasm.greeting` is not exported"), "{err}");
+
+    let glue = glue.replace("wasm.greeting", "1");
+    let optimized = optimize_js(glue, Some(&symbols), &Default::default()).unwrap();
+    assert!(optimized.contains("imports.a = {}"), "{optimized}");
+    assert!(optimized.contains("imports.a.b ="), "{optimized}");
+    assert!(optimized.contains("wasm.c(1)"), "{optimized}");
+}
+
+#[test]
+fn count_wasm_symbols_in_glue() {
+    let glue = r#"
+        imports.wbg = {};
+        imports.wbg.__wbg_log_1234 = (a) => console.log(a);
+        export const greet = (s) => wasm.greet(wasm.__wbindgen_malloc(s), wasm.__wbindgen_malloc(1));
+    "#;
+    let usage = count_wasm_symbols(glue).unwrap();
+    assert_eq!(usage.modules["wbg"], 2);
+    assert_eq!(
+        usage.imports[&("wbg".to_owned(), "__wbg_log_1234".to_owned())],
+        1
+    );
+    assert_eq!(usage.exports["__wbindgen_malloc"], 2);
+    assert_eq!(usage.exports["greet"], 1);
+    assert!(!usage.escaped);
+
+    assert!(!usage.returned);
+
+    let usage = count_wasm_symbols("let wasm; export const exports = () => wasm;").unwrap();
+    assert!(usage.escaped);
+
+    let usage = count_wasm_symbols(include_str!("corpus/glue.js")).unwrap();
+    assert!(!usage.escaped);
+    assert!(usage.returned);
+    assert_eq!(usage.exports["greet"], 1);
+}
+
+#[test]
+fn remove_unused_imports() {
+    let symbols = SymbolMap {
+        imports: HashMap::from([(
+            "wbg".to_owned(),
+            (
+                "a".to_owned(),
+                HashMap::from([
+                    ("__wbg_log_1234".to_owned(), "b".to_owned()),
+                    ("__wbg_alert_5678".to_owned(), "c".to_owned()),
+                    ("__wbg_warn_9abc".to_owned(), "c".to_owned()),
+                ]),
+            ),
+        )]),
+        exports: HashMap::new(),
+        unused_imports: HashSet::from([
+            ("wbg".to_owned(), "__wbg_alert_5678".to_owned()),
+            ("wbg".to_owned(), "__wbg_warn_9abc".to_owned()),
+        ]),
+    };
+    let glue = r#"
+        function getImports() {
+            const imports = {};
+            imports.wbg = {};
+            imports.wbg.__wbg_log_1234 = (a) => console.log(a);
+            imports.wbg.__wbg_alert_5678 = (a) => alert(a);
+            imports.wbg.__wbg_warn_9abc = (a) => console.warn(a);
+            return imports;
+        }
+    "#;
+    let optimized = optimize_js(glue, Some(&symbols), &Default::default()).unwrap();
+    assert!(optimized.contains("imports.a.b ="), "{optimized}");
+    assert_eq!(optimized.matches("imports.a.c =").count(), 1, "{optimized}");
+    assert!(!optimized.contains("alert"), "{optimized}");
+    assert!(!optimized.contains("warn"), "{optimized}");
+}
+
+// each pass against `snapshots/<pass>.in.js`. the expected `.out.js` goes through
+// the same parser and emitter so that formatting does not matter.
+#[test]
+fn glue_snapshots() {
+    macro_rules! snapshot {
+        ($($pass:ident),*) => {$({
+            let options = JsOptions {
+                $pass: true,
+                ..Default::default()
+            };
+            let cm: Lrc<SourceMap> = Default::default();
+            let input = include_str!(concat!("snapshots/", stringify!($pass), ".in.js"));
+            let expected = include_str!(concat!("snapshots/", stringify!($pass), ".out.js"));
+            let actual = transform(&cm, "in.js", input.to_owned(), None, &options).unwrap();
+            let expected =
+                transform(&cm, "out.js", expected.to_owned(), None, &Default::default()).unwrap();
+            assert_eq!(
+                emit(cm.clone(), &actual, None, false).unwrap(),
+                emit(cm.clone(), &expected, None, false).unwrap(),
+                stringify!($pass),
+            );
+        })*};
+    }
+    snapshot!(var_to_let, inline_functions, text_codec, unused_helpers);
+}
+
+#[test]
+fn intern_strings() {
+    assert!(interning_saves("undefined".len(), 8) < 0.0);
+    assert!(interning_saves("undefined".len(), 16) > 0.0);
+
+    let options = JsOptions {
+        intern_strings: true,
+        ..Default::default()
+    };
+    let js = r#"
+        'use strict';
+        const __minifier_str0 = 0;
+        export const kinds = ['function', 'function', 'function', 'function', 'function'];
+        export const more = ['function', 'function', 'function', 'function', 'function'];
+        export const most = ['function', 'function', 'function', 'function', 'function'];
+        export const last = ['function', 'object', 'object'];
+    "#;
+    let optimized = optimize_js(js, None, &options).unwrap();
+    assert_eq!(optimized.matches("function").count(), 1, "{optimized}");
+    assert_eq!(optimized.matches("'object'").count(), 2, "{optimized}");
+    assert!(optimized.contains("__minifier_str1 = "), "{optimized}");
+    assert!(
+        optimized.trim_start().starts_with("'use strict'"),
+        "{optimized}"
+    );
+
+    let imported = format!("import {{ log }} from './log.js';\n{js}");
+    let optimized = optimize_js(imported, None, &options).unwrap();
+    assert_eq!(optimized.matches("'function'").count(), 16, "{optimized}");
+}
@@ -0,0 +1,39 @@
+let wasm;
+
+function isLikeNone(x) {
+    return x === undefined || x === null;
+}
+
+function finalizeInit(instance) {
+    wasm = instance.exports;
+    return wasm;
+}
+
+function getUint8Memory0() {
+    return new Uint8Array(wasm.memory.buffer);
+}
+
+export function bytes(ptr, len) {
+    return (getUint8Memory0().subarray(ptr / 1, ptr / 1 + len));
+}
+
+export function view() {
+    return getUint8Memory0();
+}
+
+export function check(a) {
+    return isLikeNone(a) || isLikeNone(a.b);
+}
+
+function ignore(x) {
+}
+
+// reading a missing global throws
+export function guard() {
+    ignore(maybeGlobal);
+}
+
+export function init(imports) {
+    void 0;
+    return finalizeInit(imports.instance);
+}
@@ -0,0 +1,9 @@
+const cachedTextDecoder = (new TextDecoder('utf-8', { ignoreBOM: true, fatal: true }));
+
+{ cachedTextDecoder.decode(); };
+
+const cachedTextEncoder = (new TextEncoder());
+
+const encodeString = (function (arg, view) {
+    return cachedTextEncoder.encodeInto(arg, view);
+});
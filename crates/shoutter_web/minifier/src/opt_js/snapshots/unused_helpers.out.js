@@ -0,0 +1,20 @@
+let wasm;
+
+const worker = new Worker('worker.js');
+
+// property reads may run a getter
+const flags = { on: true };
+const on = flags.on;
+
+let cachedUint8Memory0 = null;
+
+function getUint8Memory0() {
+    if (cachedUint8Memory0 === null || cachedUint8Memory0.byteLength === 0) {
+        cachedUint8Memory0 = new Uint8Array(wasm.memory.buffer);
+    }
+    return cachedUint8Memory0;
+}
+
+export function greet(ptr, len) {
+    return getUint8Memory0().subarray(ptr, ptr + len);
+}
@@ -0,0 +1,27 @@
+import { log } from './snippets/log.js';
+
+var wasm;
+var cachedUint8Memory0 = null;
+var WASM_VECTOR_LEN = 0;
+var TEXT = 'utf-8', LIMIT = 128;
+
+function getUint8Memory0() {
+    if (cachedUint8Memory0 === null || cachedUint8Memory0.byteLength === 0) {
+        cachedUint8Memory0 = new Uint8Array(wasm.memory.buffer);
+    }
+    return cachedUint8Memory0;
+}
+
+// read before its initialization, which would throw once it is a `let`
+var alias = next;
+var next = 1;
+
+// code may run from here on, reading what is declared below
+log(TEXT, LIMIT, WASM_VECTOR_LEN);
+
+var late = getUint8Memory0();
+
+function local() {
+    var x = 1;
+    return x;
+}
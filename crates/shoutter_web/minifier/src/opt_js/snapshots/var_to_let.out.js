@@ -0,0 +1,25 @@
+import { log } from './snippets/log.js';
+
+let wasm;
+let cachedUint8Memory0 = null;
+const WASM_VECTOR_LEN = 0;
+const TEXT = 'utf-8', LIMIT = 128;
+
+function getUint8Memory0() {
+    if (cachedUint8Memory0 === null || cachedUint8Memory0.byteLength === 0) {
+        cachedUint8Memory0 = new Uint8Array(wasm.memory.buffer);
+    }
+    return cachedUint8Memory0;
+}
+
+var alias = next;
+var next = 1;
+
+log(TEXT, LIMIT, WASM_VECTOR_LEN);
+
+var late = getUint8Memory0();
+
+function local() {
+    var x = 1;
+    return x;
+}
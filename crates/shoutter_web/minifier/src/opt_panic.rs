@@ -0,0 +1,176 @@
+// strips panic messages and locations out of wasm data.
+//
+// rust embeds `core::panic::Location { file: &str, line: u32, col: u32 }` of every
+// panicking call as 16 bytes of static data, and passes messages to `core::panicking`
+// as `i32.const msg; i32.const len; i32.const &LOCATION`.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::opt_data::{overlaps, read, read_mut, words, References};
+
+// texts shorter than this are kept, placeholders are never longer.
+const MIN_TEXT_LEN: u32 = 8;
+const MAX_TEXT_LEN: u32 = 4096;
+
+/// Replaces file paths of panic locations and panic messages in `segments` with
+/// placeholders such as `#12`. Returns placeholder => original text, and the new length
+/// of each message by its (ptr, len).
+///
+/// Paths are shortened through the length in their locations, messages through the
+/// `i32.const` following their pointer in code. The rest of each text is zeroed to be left
+/// out by `opt_data::compact`.
+pub fn strip(
+    segments: &mut BTreeMap<u32, Vec<u8>>,
+    references: &References,
+) -> (BTreeMap<String, String>, HashMap<(i32, i32), i32>) {
+    let words = words(segments);
+    let locations = locations(segments);
+
+    // (ptr, len) of a file => addresses of its locations
+    let mut files = BTreeMap::<_, Vec<_>>::new();
+    for (addr, file) in &locations {
+        files.entry(*file).or_default().push(*addr);
+    }
+    // (ptr, len) of messages passed along with a location
+    let mut messages = BTreeSet::new();
+    for [ptr, len, location] in &references.triples {
+        if locations.contains_key(location) {
+            messages.insert((*ptr, *len));
+        }
+    }
+
+    let mut ranges = references.pairs();
+    ranges.extend(files.keys().copied());
+    ranges.sort();
+    ranges.dedup();
+    // nothing else may refer to the text or into it
+    let untouched = |ptr: u32, len: u32| {
+        (MIN_TEXT_LEN..=MAX_TEXT_LEN).contains(&len)
+            && !references.fixed.contains(&ptr)
+            && (ptr.saturating_add(1)..ptr.saturating_add(len)).all(|x| {
+                !words.contains_key(&x)
+                    && !references.consts.contains_key(&x)
+                    && !references.fixed.contains(&x)
+            })
+            && !overlaps(&ranges, ptr, len)
+    };
+
+    let mut placeholders = Placeholders::default();
+    let mut lens = HashMap::new();
+    for ((ptr, len), addrs) in &files {
+        // only the locations hold the pointer
+        if words.get(ptr) != Some(&addrs.len())
+            || references.consts.contains_key(ptr)
+            || !untouched(*ptr, *len)
+        {
+            continue;
+        }
+        let Some(placeholder) = placeholders.replace(segments, *ptr, *len) else { continue };
+        for addr in addrs {
+            read_mut(segments, addr + 4, 4)
+                .unwrap()
+                .copy_from_slice(&(placeholder.len() as u32).to_le_bytes());
+        }
+    }
+    for (ptr, len) in &messages {
+        let always_with_len = references
+            .consts
+            .get(ptr)
+            .map_or(false, |x| x.iter().all(|x| *x == Some(*len)));
+        if !always_with_len || words.contains_key(ptr) || !untouched(*ptr, *len) {
+            continue;
+        }
+        if let Some(placeholder) = placeholders.replace(segments, *ptr, *len) {
+            lens.insert((*ptr as i32, *len as i32), placeholder.len() as i32);
+        }
+    }
+    (placeholders.originals, lens)
+}
+
+#[derive(Default)]
+struct Placeholders {
+    /// original => placeholder
+    assigned: HashMap<String, String>,
+    /// placeholder => original
+    originals: BTreeMap<String, String>,
+}
+
+impl Placeholders {
+    // writes the placeholder of the text at `ptr` over it, zeroing the rest.
+    fn replace(
+        &mut self,
+        segments: &mut BTreeMap<u32, Vec<u8>>,
+        ptr: u32,
+        len: u32,
+    ) -> Option<String> {
+        let bytes = read_mut(segments, ptr, len)?;
+        let text = std::str::from_utf8(bytes).ok()?.to_owned();
+        let placeholder = self
+            .assigned
+            .entry(text.clone())
+            .or_insert_with(|| format!("#{}", self.originals.len()))
+            .clone();
+        self.originals.insert(placeholder.clone(), text);
+
+        bytes.fill(0);
+        bytes[..placeholder.len()].copy_from_slice(placeholder.as_bytes());
+        Some(placeholder)
+    }
+}
+
+// address => (ptr, len) of the file of `Location`s found in data.
+fn locations(segments: &BTreeMap<u32, Vec<u8>>) -> BTreeMap<u32, (u32, u32)> {
+    let mut locations = BTreeMap::new();
+    for (offset, data) in segments {
+        let mut i = (4 - offset % 4) as usize % 4;
+        while i + 16 <= data.len() {
+            let [ptr, len, line, col] = [0, 4, 8, 12]
+                .map(|x| u32::from_le_bytes(data[i + x..i + x + 4].try_into().unwrap()));
+            let is_location = (1..=1 << 20).contains(&line)
+                && (1..=1 << 16).contains(&col)
+                && (1..=MAX_TEXT_LEN).contains(&len)
+                && read(segments, ptr, len)
+                    .and_then(|x| std::str::from_utf8(x).ok())
+                    .map_or(false, |x| x.ends_with(".rs"));
+            if is_location {
+                locations.insert(offset + i as u32, (ptr, len));
+                i += 16;
+            } else {
+                i += 4;
+            }
+        }
+    }
+    locations
+}
+
+#[test]
+fn strip_panics() {
+    let message = b"called `Option::unwrap()` on a `None` value";
+    let location = [0x200u32, 10, 12, 5]
+        .iter()
+        .flat_map(|x| x.to_le_bytes())
+        .collect::<Vec<_>>();
+    let mut segments = BTreeMap::from([
+        (0x100, location),
+        (0x200, b"src/lib.rs".to_vec()),
+        (0x300, message.to_vec()),
+    ]);
+    let len = message.len() as u32;
+    let references = References {
+        consts: HashMap::from([
+            (0x300, vec![Some(len)]),
+            (len, vec![Some(0x100)]),
+            (0x100, vec![None]),
+        ]),
+        triples: vec![[0x300, len, 0x100]],
+        ..Default::default()
+    };
+
+    let (originals, lens) = strip(&mut segments, &references);
+    assert_eq!(originals["#0"], "src/lib.rs");
+    assert_eq!(originals["#1"], std::str::from_utf8(message).unwrap());
+    assert_eq!(lens, HashMap::from([((0x300, len as i32), 2)]));
+    assert_eq!(segments[&0x100][4..8], 2u32.to_le_bytes());
+    assert_eq!(&segments[&0x200][..2], b"#0");
+    assert_eq!(&segments[&0x300][..3], b"#1\0");
+}
@@ -0,0 +1,291 @@
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
+
+use anyhow::{ensure, Result};
+use wasm_encoder::Instruction;
+use wasmparser::Operator;
+
+use crate::symbol::map_val_type;
+
+/// Rewrites a function body. Operators left as they are keep their original bytes.
+///
+/// With `optimize`, removes code after `unreachable`, `br`, `return`, etc., folds
+/// arithmetic of two `i32.const`s and drops locals never used.
+/// `consts` replaces operands of `i32.const` and `lens` those of the second of two
+/// `i32.const`s in a row (see `opt_data`).
+pub fn rewrite_function(
+    body: &wasmparser::FunctionBody,
+    param_count: u32,
+    optimize: bool,
+    consts: &HashMap<i32, i32>,
+    lens: &HashMap<(i32, i32), i32>,
+) -> Result<wasm_encoder::Function> {
+    let mut reader = body.get_binary_reader();
+    let bytes = reader.read_bytes(reader.bytes_remaining())?;
+    let base = body.range().start;
+
+    let mut locals = vec![];
+    for local in body.get_locals_reader()? {
+        let (count, ty) = local?;
+        locals.push((count, map_val_type(ty)));
+    }
+
+    let mut ops = vec![];
+    let mut reader = body.get_operators_reader()?;
+    while !reader.eof() {
+        let (op, offset) = reader.read_with_offset()?;
+        ops.push((op, offset - base));
+    }
+    let end = reader.original_position() - base;
+    let ranges = ops
+        .iter()
+        .map(|(_, start)| *start)
+        .skip(1)
+        .chain([end])
+        .zip(&ops)
+        .map(|(end, (_, start))| *start..end);
+    let ops = ops.iter().map(|(op, _)| op).zip(ranges).collect::<Vec<_>>();
+
+    if !optimize && consts.is_empty() && lens.is_empty() {
+        let mut function = wasm_encoder::Function::new(locals);
+        function.raw(
+            ops.iter()
+                .flat_map(|(_, r)| bytes[r.clone()].iter().copied()),
+        );
+        return Ok(function);
+    }
+
+    let items = to_items(&ops, optimize, consts, lens);
+    let (locals, remap) = if optimize {
+        compact_locals(locals, &items, param_count)?
+    } else {
+        (locals, vec![])
+    };
+    let local_index = |i: u32| match i.checked_sub(param_count).map(|x| remap.get(x as usize)) {
+        Some(Some(Some(i))) => *i,
+        _ => i,
+    };
+
+    let mut function = wasm_encoder::Function::new(locals);
+    for item in items {
+        match item {
+            Item::Raw(r) => {
+                function.raw(bytes[r].iter().copied());
+            }
+            Item::Local(LocalOp::Get, i) => {
+                function.instruction(&Instruction::LocalGet(local_index(i)));
+            }
+            Item::Local(LocalOp::Set, i) => {
+                function.instruction(&Instruction::LocalSet(local_index(i)));
+            }
+            Item::Local(LocalOp::Tee, i) => {
+                function.instruction(&Instruction::LocalTee(local_index(i)));
+            }
+            Item::I32Const(value) => {
+                function.instruction(&Instruction::I32Const(value));
+            }
+        }
+    }
+    Ok(function)
+}
+
+// drops locals never used. parameters are never removed.
+// returns the new declarations and new indices of the old locals.
+fn compact_locals(
+    locals: Vec<(u32, wasm_encoder::ValType)>,
+    items: &[Item],
+    param_count: u32,
+) -> Result<(Vec<(u32, wasm_encoder::ValType)>, Vec<Option<u32>>)> {
+    let used = items
+        .iter()
+        .filter_map(|x| match x {
+            Item::Local(_, i) if *i >= param_count => Some(*i),
+            _ => None,
+        })
+        .collect::<BTreeSet<_>>();
+    let mut local_types = vec![];
+    for (count, ty) in locals {
+        ensure!(
+            local_types.len() + count as usize <= u32::MAX as usize,
+            "too many locals"
+        );
+        local_types.extend((0..count).map(|_| ty));
+    }
+    let mut remap = vec![None; local_types.len()];
+    let mut compacted = Vec::<(u32, wasm_encoder::ValType)>::new();
+    for (n, &i) in used.iter().enumerate() {
+        let ty = *local_types
+            .get((i - param_count) as usize)
+            .ok_or_else(|| anyhow::anyhow!("local {i} is not declared"))?;
+        remap[(i - param_count) as usize] = Some(param_count + n as u32);
+        match compacted.last_mut() {
+            Some((count, last)) if *last == ty => *count += 1,
+            _ => compacted.push((1, ty)),
+        }
+    }
+    Ok((compacted, remap))
+}
+
+enum LocalOp {
+    Get,
+    Set,
+    Tee,
+}
+
+enum Item {
+    Raw(Range<usize>),
+    Local(LocalOp, u32),
+    I32Const(i32),
+}
+
+fn to_items(
+    ops: &[(&Operator, Range<usize>)],
+    optimize: bool,
+    consts: &HashMap<i32, i32>,
+    lens: &HashMap<(i32, i32), i32>,
+) -> Vec<Item> {
+    let mut items = vec![];
+    // nesting depth inside dead code
+    let mut dead: Option<usize> = None;
+
+    for (i, (op, range)) in ops.iter().enumerate() {
+        if let Some(depth) = &mut dead {
+            match op {
+                Operator::Block { .. }
+                | Operator::Loop { .. }
+                | Operator::If { .. }
+                | Operator::Try { .. } => {
+                    *depth += 1;
+                    continue;
+                }
+                Operator::End | Operator::Delegate { .. } if *depth > 0 => {
+                    *depth -= 1;
+                    continue;
+                }
+                Operator::End
+                | Operator::Delegate { .. }
+                | Operator::Else
+                | Operator::Catch { .. }
+                | Operator::CatchAll
+                    if *depth == 0 =>
+                {
+                    dead = None;
+                }
+                _ => continue,
+            }
+        }
+
+        let item = match op {
+            Operator::LocalGet { local_index } => Item::Local(LocalOp::Get, *local_index),
+            Operator::LocalSet { local_index } => Item::Local(LocalOp::Set, *local_index),
+            Operator::LocalTee { local_index } => Item::Local(LocalOp::Tee, *local_index),
+            Operator::I32Const { value } => {
+                let len = match i.checked_sub(1).map(|x| ops[x].0) {
+                    Some(Operator::I32Const { value: ptr }) => lens.get(&(*ptr, *value)),
+                    _ => None,
+                };
+                Item::I32Const(*len.or(consts.get(value)).unwrap_or(value))
+            }
+            _ if !optimize => Item::Raw(range.clone()),
+            op => match (fold_i32(op), &items[..]) {
+                (Some(f), [.., Item::I32Const(a), Item::I32Const(b)]) => {
+                    let folded = f(*a, *b);
+                    items.truncate(items.len() - 2);
+                    Item::I32Const(folded)
+                }
+                _ => Item::Raw(range.clone()),
+            },
+        };
+        items.push(item);
+
+        if optimize
+            && matches!(
+                op,
+                Operator::Unreachable
+                    | Operator::Br { .. }
+                    | Operator::BrTable { .. }
+                    | Operator::Return
+                    | Operator::Throw { .. }
+                    | Operator::Rethrow { .. }
+                    | Operator::ReturnCall { .. }
+                    | Operator::ReturnCallIndirect { .. }
+            )
+        {
+            dead = Some(0);
+        }
+    }
+    items
+}
+
+// binary operators that never trap
+fn fold_i32(op: &Operator) -> Option<fn(i32, i32) -> i32> {
+    let f: fn(i32, i32) -> i32 = match op {
+        Operator::I32Add => i32::wrapping_add,
+        Operator::I32Sub => i32::wrapping_sub,
+        Operator::I32Mul => i32::wrapping_mul,
+        Operator::I32And => |a, b| a & b,
+        Operator::I32Or => |a, b| a | b,
+        Operator::I32Xor => |a, b| a ^ b,
+        Operator::I32Shl => |a, b| a.wrapping_shl(b as u32),
+        Operator::I32ShrS => |a, b| a.wrapping_shr(b as u32),
+        Operator::I32ShrU => |a, b| ((a as u32).wrapping_shr(b as u32)) as i32,
+        _ => return None,
+    };
+    Some(f)
+}
+
+#[test]
+fn optimize_function() {
+    use wasm_encoder::{CodeSection, Function, FunctionSection, Module, TypeSection, ValType};
+
+    let mut function = Function::new([(1, ValType::I64), (2, ValType::I32)]);
+    function
+        .instruction(&Instruction::I32Const(40))
+        .instruction(&Instruction::I32Const(2))
+        .instruction(&Instruction::I32Add)
+        .instruction(&Instruction::LocalSet(3))
+        .instruction(&Instruction::LocalGet(3))
+        .instruction(&Instruction::LocalGet(0))
+        .instruction(&Instruction::I32Add)
+        .instruction(&Instruction::Return)
+        .instruction(&Instruction::Block(wasm_encoder::BlockType::Empty))
+        .instruction(&Instruction::LocalGet(1))
+        .instruction(&Instruction::Drop)
+        .instruction(&Instruction::End)
+        .instruction(&Instruction::I32Const(0))
+        .instruction(&Instruction::End);
+
+    let mut module = Module::new();
+    let mut types = TypeSection::new();
+    types.function([ValType::I32], [ValType::I32]);
+    module.section(&types);
+    let mut functions = FunctionSection::new();
+    functions.function(0);
+    module.section(&functions);
+    let mut code = CodeSection::new();
+    code.function(&function);
+    module.section(&code);
+    let wasm = module.finish();
+
+    for payload in wasmparser::Parser::new(0).parse_all(&wasm) {
+        let wasmparser::Payload::CodeSectionEntry(body) = payload.unwrap() else { continue };
+
+        let copied = rewrite_function(&body, 1, false, &HashMap::new(), &HashMap::new()).unwrap();
+        assert_eq!(copied.byte_len(), body.range().len());
+
+        let optimized = rewrite_function(&body, 1, true, &HashMap::new(), &HashMap::new()).unwrap();
+        let mut expected = Function::new([(1, ValType::I32)]);
+        expected
+            .instruction(&Instruction::I32Const(42))
+            .instruction(&Instruction::LocalSet(1))
+            .instruction(&Instruction::LocalGet(1))
+            .instruction(&Instruction::LocalGet(0))
+            .instruction(&Instruction::I32Add)
+            .instruction(&Instruction::Return)
+            .instruction(&Instruction::End);
+        let (mut a, mut b) = (vec![], vec![]);
+        wasm_encoder::Encode::encode(&optimized, &mut a);
+        wasm_encoder::Encode::encode(&expected, &mut b);
+        assert_eq!(a, b);
+    }
+}
@@ -0,0 +1,482 @@
+// passes each file goes through, picked by extension and run in order.
+//
+// to add a pass, implement `Pass` and list it in `Pipeline::new`.
+
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use anyhow::{bail, ensure, Result};
+
+use crate::config::Config;
+use crate::error::{MinifyError, Phase, ResultExt};
+use crate::opt_class::ClassMap;
+use crate::opt_html::HtmlOptions;
+use crate::opt_js::JsOptions;
+use crate::report::PassStats;
+use crate::symbol::{SymbolMap, SymbolUsage};
+use crate::{opt_css, opt_html, opt_js, symbol, sys, TrackedFile};
+
+pub type PassFuture<'a> = Pin<Box<dyn Future<Output = Result<()>> + 'a>>;
+
+/// A step rewriting `TrackedFile::content` of one kind of file.
+pub trait Pass {
+    /// used by `MINIFIER_SKIP_PASSES` and in the report.
+    fn name(&self) -> &'static str;
+    /// extensions of the files it runs on, e.g. `js`.
+    fn extensions(&self) -> &'static [&'static str];
+    /// reported when the pass fails.
+    fn phase(&self) -> Phase;
+    fn run<'a>(
+        &'a self,
+        file: &'a mut TrackedFile,
+        shared: &'a mut Shared,
+        config: &'a Config,
+    ) -> PassFuture<'a>;
+}
+
+/// What passes hand over to each other, across a wasm-bindgen pair or all files.
+#[derive(Default)]
+pub struct Shared {
+    /// renamed classes, collected from every file before any pass runs.
+    pub classes: Option<Rc<ClassMap>>,
+    /// wasm symbols used by the glue, counted before the wasm passes run.
+    pub usage: Option<SymbolUsage>,
+    /// renamed wasm symbols, for the glue passes.
+    pub symbols: Option<SymbolMap>,
+}
+
+// runs before minifiers, which may merge rules or quote attributes differently.
+struct MangleClasses;
+impl Pass for MangleClasses {
+    fn name(&self) -> &'static str {
+        "mangle-classes"
+    }
+    fn extensions(&self) -> &'static [&'static str] {
+        &["css", "html", "wasm"]
+    }
+    fn phase(&self) -> Phase {
+        Phase::Classes
+    }
+    fn run<'a>(
+        &'a self,
+        file: &'a mut TrackedFile,
+        shared: &'a mut Shared,
+        _config: &'a Config,
+    ) -> PassFuture<'a> {
+        Box::pin(async move {
+            let Some(classes) = &shared.classes else { return Ok(()) };
+            match file.path.extension().and_then(|x| x.to_str()) {
+                Some("wasm") => classes.rename_wasm(&mut file.content)?,
+                Some("html") => {
+                    let html = String::from_utf8(file.content.clone())?;
+                    file.content = classes.rename_html(&html)?.into_bytes();
+                }
+                _ => {
+                    let css = String::from_utf8(file.content.clone())?;
+                    file.content = classes.rename_css(&css).into_bytes();
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+struct WasmSymbols;
+impl Pass for WasmSymbols {
+    fn name(&self) -> &'static str {
+        "wasm-symbols"
+    }
+    fn extensions(&self) -> &'static [&'static str] {
+        &["wasm"]
+    }
+    fn phase(&self) -> Phase {
+        Phase::Symbol
+    }
+    fn run<'a>(
+        &'a self,
+        file: &'a mut TrackedFile,
+        shared: &'a mut Shared,
+        config: &'a Config,
+    ) -> PassFuture<'a> {
+        Box::pin(async move {
+            // exports of a wasm without glue can't be renamed.
+            let Some(usage) = &shared.usage else { return Ok(()) };
+            let minified = symbol::minify_symbol(&mut file.content, Some(usage), &config.symbol)?;
+            file.sections = minified.sections;
+            file.panics = minified.panics;
+            shared.symbols = Some(minified.symbols);
+            Ok(())
+        })
+    }
+}
+
+// swc. glue passes and wasm symbol renaming apply to wasm-bindgen glue only.
+// with `minify`, swc compresses and mangles too, leaving nothing for terser.
+struct OptimizeJs {
+    minify: bool,
+}
+impl Pass for OptimizeJs {
+    fn name(&self) -> &'static str {
+        "optimize-js"
+    }
+    fn extensions(&self) -> &'static [&'static str] {
+        &["js"]
+    }
+    fn phase(&self) -> Phase {
+        Phase::OptimizeJs
+    }
+    fn run<'a>(
+        &'a self,
+        file: &'a mut TrackedFile,
+        shared: &'a mut Shared,
+        config: &'a Config,
+    ) -> PassFuture<'a> {
+        Box::pin(async move {
+            let js = String::from_utf8(file.content.clone())?;
+            let mut options = match shared.usage {
+                Some(_) => config.js.clone(),
+                None => JsOptions::default(),
+            };
+            options.minify = self.minify;
+            let options = &options;
+            let symbols = shared.symbols.as_ref();
+            if config.source_map {
+                let (optimized, source_map) =
+                    opt_js::optimize_js_with_source_map(js, &file.file_name(), symbols, options)?;
+                file.content = optimized.into_bytes();
+                file.source_map = Some(source_map);
+            } else {
+                file.content = opt_js::optimize_js(js, symbols, options)?.into_bytes();
+            }
+            Ok(())
+        })
+    }
+}
+
+// continues the source map of an earlier pass, if any, or starts one.
+struct Terser;
+impl Pass for Terser {
+    fn name(&self) -> &'static str {
+        "terser"
+    }
+    fn extensions(&self) -> &'static [&'static str] {
+        &["js"]
+    }
+    fn phase(&self) -> Phase {
+        Phase::Terser
+    }
+    fn run<'a>(
+        &'a self,
+        file: &'a mut TrackedFile,
+        _shared: &'a mut Shared,
+        config: &'a Config,
+    ) -> PassFuture<'a> {
+        Box::pin(async move {
+            let js = String::from_utf8(file.content.clone())?;
+            if config.source_map || file.source_map.is_some() {
+                let (minified, source_map) = sys::minifier::js_with_source_map(
+                    &js,
+                    &file.file_name(),
+                    file.source_map.as_ref(),
+                )
+                .await?;
+                file.content = minified.into_bytes();
+                file.source_map = Some(source_map);
+            } else {
+                file.content = sys::minifier::js(&js).await?.into_bytes();
+            }
+            Ok(())
+        })
+    }
+}
+
+struct HtmlMinifierTerser;
+impl Pass for HtmlMinifierTerser {
+    fn name(&self) -> &'static str {
+        "html-minifier"
+    }
+    fn extensions(&self) -> &'static [&'static str] {
+        &["html"]
+    }
+    fn phase(&self) -> Phase {
+        Phase::Html
+    }
+    fn run<'a>(
+        &'a self,
+        file: &'a mut TrackedFile,
+        _shared: &'a mut Shared,
+        _config: &'a Config,
+    ) -> PassFuture<'a> {
+        Box::pin(async move {
+            let html = String::from_utf8(file.content.clone())?;
+            file.content = sys::minifier::html(&html).await?.into_bytes();
+            Ok(())
+        })
+    }
+}
+
+struct OptimizeHtml;
+impl Pass for OptimizeHtml {
+    fn name(&self) -> &'static str {
+        "optimize-html"
+    }
+    fn extensions(&self) -> &'static [&'static str] {
+        &["html"]
+    }
+    fn phase(&self) -> Phase {
+        Phase::Html
+    }
+    fn run<'a>(
+        &'a self,
+        file: &'a mut TrackedFile,
+        _shared: &'a mut Shared,
+        _config: &'a Config,
+    ) -> PassFuture<'a> {
+        Box::pin(async move {
+            let html = String::from_utf8(file.content.clone())?;
+            file.content = opt_html::minify_html(&html, &HtmlOptions::default())?.into_bytes();
+            Ok(())
+        })
+    }
+}
+
+struct CleanCss;
+impl Pass for CleanCss {
+    fn name(&self) -> &'static str {
+        "clean-css"
+    }
+    fn extensions(&self) -> &'static [&'static str] {
+        &["css"]
+    }
+    fn phase(&self) -> Phase {
+        Phase::Css
+    }
+    fn run<'a>(
+        &'a self,
+        file: &'a mut TrackedFile,
+        _shared: &'a mut Shared,
+        _config: &'a Config,
+    ) -> PassFuture<'a> {
+        Box::pin(async move {
+            let css = String::from_utf8(file.content.clone())?;
+            file.content = sys::minifier::css(&css).await?.into_bytes();
+            Ok(())
+        })
+    }
+}
+
+struct LightningCss;
+impl Pass for LightningCss {
+    fn name(&self) -> &'static str {
+        "lightningcss"
+    }
+    fn extensions(&self) -> &'static [&'static str] {
+        &["css"]
+    }
+    fn phase(&self) -> Phase {
+        Phase::Css
+    }
+    fn run<'a>(
+        &'a self,
+        file: &'a mut TrackedFile,
+        _shared: &'a mut Shared,
+        _config: &'a Config,
+    ) -> PassFuture<'a> {
+        Box::pin(async move {
+            let css = String::from_utf8(file.content.clone())?;
+            file.content = opt_css::minify_css(&css, &file.file_name())?.into_bytes();
+            Ok(())
+        })
+    }
+}
+
+/// Which pass minifies html. `html-minifier` needs node; `optimize-html` runs anywhere.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HtmlMinifier {
+    #[default]
+    HtmlMinifierTerser,
+    OptimizeHtml,
+}
+
+impl FromStr for HtmlMinifier {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "html-minifier" => Ok(HtmlMinifier::HtmlMinifierTerser),
+            "optimize-html" => Ok(HtmlMinifier::OptimizeHtml),
+            _ => bail!("unknown html minifier: {s}"),
+        }
+    }
+}
+
+/// Which minifies javascript after `optimize-js`. `terser` needs node; `swc` runs
+/// anywhere, as part of `optimize-js`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum JsMinifier {
+    #[default]
+    Terser,
+    Swc,
+}
+
+impl FromStr for JsMinifier {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "terser" => Ok(JsMinifier::Terser),
+            "swc" => Ok(JsMinifier::Swc),
+            _ => bail!("unknown javascript minifier: {s}"),
+        }
+    }
+}
+
+/// Which pass minifies css. `clean-css` needs node; `lightningcss` runs anywhere.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CssMinifier {
+    #[default]
+    CleanCss,
+    LightningCss,
+}
+
+impl FromStr for CssMinifier {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "clean-css" => Ok(CssMinifier::CleanCss),
+            "lightningcss" => Ok(CssMinifier::LightningCss),
+            _ => bail!("unknown css minifier: {s}"),
+        }
+    }
+}
+
+pub struct Pipeline {
+    passes: Vec<Box<dyn Pass>>,
+}
+
+impl Pipeline {
+    /// Every pass except `skipped`, given by name. `mangle-classes` runs only if
+    /// `mangle_classes`.
+    pub fn new(
+        js: JsMinifier,
+        html: HtmlMinifier,
+        css: CssMinifier,
+        mangle_classes: bool,
+        skipped: &[String],
+    ) -> Result<Self> {
+        let mut all: Vec<Box<dyn Pass>> = vec![];
+        if mangle_classes {
+            all.push(Box::new(MangleClasses));
+        }
+        all.push(Box::new(WasmSymbols));
+        match js {
+            JsMinifier::Terser => {
+                all.push(Box::new(OptimizeJs { minify: false }));
+                all.push(Box::new(Terser));
+            }
+            JsMinifier::Swc => all.push(Box::new(OptimizeJs { minify: true })),
+        }
+        all.push(match html {
+            HtmlMinifier::HtmlMinifierTerser => Box::new(HtmlMinifierTerser),
+            HtmlMinifier::OptimizeHtml => Box::new(OptimizeHtml),
+        });
+        all.push(match css {
+            CssMinifier::CleanCss => Box::new(CleanCss),
+            CssMinifier::LightningCss => Box::new(LightningCss),
+        });
+        for name in skipped {
+            if name != "mangle-classes" && !all.iter().any(|x| x.name() == name) {
+                bail!("unknown pass: {name}");
+            }
+        }
+        let is_skipped = |name: &str| skipped.iter().any(|x| x == name);
+        // the glue would keep calling wasm symbols by their old names.
+        ensure!(
+            !is_skipped("optimize-js") || is_skipped("wasm-symbols"),
+            "`wasm-symbols` must be skipped along with `optimize-js`"
+        );
+        Ok(Self {
+            passes: all.into_iter().filter(|x| !is_skipped(x.name())).collect(),
+        })
+    }
+
+    pub fn has(&self, name: &str) -> bool {
+        self.passes.iter().any(|x| x.name() == name)
+    }
+
+    /// Runs the passes for the extension of `file`, recording stats of each.
+    pub async fn run(
+        &self,
+        file: &mut TrackedFile,
+        shared: &mut Shared,
+        config: &Config,
+    ) -> Result<(), MinifyError> {
+        let extension = file
+            .path
+            .extension()
+            .and_then(|x| x.to_str())
+            .map(str::to_owned);
+        for pass in &self.passes {
+            if !extension
+                .as_deref()
+                .is_some_and(|x| pass.extensions().contains(&x))
+            {
+                continue;
+            }
+            let bytes_in = file.content.len();
+            // files are processed concurrently, so a pass waiting on node (terser,
+            // clean-css, ...) reports wall time including work on other files.
+            let start = sys::process::now();
+            pass.run(file, shared, config)
+                .await
+                .phase(&file.path, pass.phase())?;
+            file.passes.push(PassStats {
+                name: pass.name().to_owned(),
+                bytes_in,
+                bytes_out: file.content.len(),
+                millis: sys::process::now() - start,
+            });
+        }
+        Ok(())
+    }
+}
+
+// the cache key is derived from `Debug` of the config.
+impl Debug for Pipeline {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.passes.iter().map(|x| x.name()))
+            .finish()
+    }
+}
+
+#[test]
+fn skip_passes() {
+    let names = |pipeline: Pipeline| format!("{pipeline:?}");
+    let (js, html, css) = Default::default();
+    assert_eq!(
+        names(Pipeline::new(js, html, css, false, &[]).unwrap()),
+        r#"["wasm-symbols", "optimize-js", "terser", "html-minifier", "clean-css"]"#
+    );
+    let rust = Pipeline::new(
+        JsMinifier::Swc,
+        HtmlMinifier::OptimizeHtml,
+        CssMinifier::LightningCss,
+        true,
+        &[],
+    );
+    assert_eq!(
+        names(rust.unwrap()),
+        r#"["mangle-classes", "wasm-symbols", "optimize-js", "optimize-html", "lightningcss"]"#
+    );
+    assert!(Pipeline::new(js, html, css, false, &["uglify".to_owned()]).is_err());
+    assert!(Pipeline::new(js, html, css, false, &["optimize-js".to_owned()]).is_err());
+    let skipped = ["mangle-classes".to_owned()];
+    assert!(!Pipeline::new(js, html, css, false, &skipped)
+        .unwrap()
+        .has("mangle-classes"));
+}
@@ -0,0 +1,340 @@
+use std::fmt::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::println;
+use crate::sys::fs;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ProcessStats {
+    pub origin_size: usize,
+    pub minified_size: Option<usize>,
+    pub brotlied_size: usize,
+}
+
+/// bytes saved by stripping or rewriting a wasm section.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SectionSaving {
+    pub name: String,
+    pub saved: i64,
+}
+
+/// size and time of one minifier pass over a file.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PassStats {
+    pub name: String,
+    pub bytes_in: usize,
+    pub bytes_out: usize,
+    pub millis: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FileReport {
+    pub file_name: String,
+    #[serde(flatten)]
+    pub stats: ProcessStats,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sections: Vec<SectionSaving>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub passes: Vec<PassStats>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Report {
+    pub files: Vec<FileReport>,
+}
+
+impl Report {
+    pub fn total(&self) -> ProcessStats {
+        let sum =
+            |f: fn(&ProcessStats) -> usize| self.files.iter().map(|x| f(&x.stats)).sum::<usize>();
+        ProcessStats {
+            origin_size: sum(|x| x.origin_size),
+            minified_size: self
+                .files
+                .iter()
+                .any(|x| x.stats.minified_size.is_some())
+                .then(|| sum(|x| x.minified_size.unwrap_or(x.origin_size))),
+            brotlied_size: sum(|x| x.brotlied_size),
+        }
+    }
+
+    // file names are compared without trunk's content hash so that reports from
+    // different builds can be matched up.
+    fn find(&self, file_name: &str) -> Option<&ProcessStats> {
+        let name = stable_name(file_name);
+        self.files
+            .iter()
+            .find(|x| stable_name(&x.file_name) == name)
+            .map(|x| &x.stats)
+    }
+
+    pub async fn read(path: &Path) -> Result<Report> {
+        let content = fs::read_file(path).await?;
+        serde_json::from_slice(&content)
+            .with_context(|| format!("failed to parse report: {}", path.display()))
+    }
+
+    // format is decided by the extension of the path.
+    pub async fn write(&self, path: &Path) -> Result<()> {
+        let content = match path.extension().and_then(|x| x.to_str()) {
+            Some("json") => serde_json::to_string_pretty(self)?,
+            Some("csv") => self.to_csv(),
+            _ => bail!("unsupported report format: {}", path.display()),
+        };
+        fs::write_file(path, content.as_bytes()).await
+    }
+
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("file_name,origin_size,minified_size,brotlied_size\n");
+        for f in &self.files {
+            writeln!(
+                csv,
+                "{},{},{},{}",
+                csv_field(&f.file_name),
+                f.stats.origin_size,
+                f.stats
+                    .minified_size
+                    .map_or_else(String::new, |x| x.to_string()),
+                f.stats.brotlied_size,
+            )
+            .unwrap();
+        }
+        csv
+    }
+
+    pub fn print(&self, baseline: Option<&Report>) {
+        let file_name_max_len = self
+            .files
+            .iter()
+            .map(|f| f.file_name.chars().count())
+            .chain(
+                self.files
+                    .iter()
+                    .flat_map(|f| &f.sections)
+                    .map(|x| x.name.len() + 2),
+            )
+            .chain(
+                self.files
+                    .iter()
+                    .flat_map(|f| &f.passes)
+                    .map(|x| x.name.len() + 2),
+            )
+            .chain(["total".len()])
+            .max()
+            .unwrap();
+
+        let mut header = format!(
+            "{1:>0$}: {2:>10} {3:>10} {4:>10}",
+            file_name_max_len, "filename", "origin", "minify", "brotli",
+        );
+        if baseline.is_some() {
+            write!(header, " {:>11}", "delta").unwrap();
+        }
+        println(header);
+
+        let print_row = |file_name: &str, stats: &ProcessStats, before: Option<&ProcessStats>| {
+            let kib = |n| format!("{:7.02}KiB", (n as f64) / 1024.0);
+            let mut row = format!(
+                "{1:>0$}: {2:>} {3:>} {4:>}",
+                file_name_max_len,
+                file_name,
+                kib(stats.origin_size),
+                stats
+                    .minified_size
+                    .map_or_else(|| format!("{:>10}", "---KiB"), kib),
+                kib(stats.brotlied_size),
+            );
+            if baseline.is_some() {
+                match before {
+                    Some(before) => {
+                        let delta = stats.brotlied_size as f64 - before.brotlied_size as f64;
+                        write!(row, " {:+8.02}KiB", delta / 1024.0).unwrap();
+                    }
+                    None => write!(row, " {:>11}", "new").unwrap(),
+                }
+            }
+            println(row);
+        };
+
+        for f in &self.files {
+            print_row(
+                &f.file_name,
+                &f.stats,
+                baseline.and_then(|b| b.find(&f.file_name)),
+            );
+            for section in &f.sections {
+                println(format!(
+                    "{1:>0$}: {2:>10} {3:+7.02}KiB",
+                    file_name_max_len,
+                    format!("[{}]", section.name),
+                    "",
+                    -section.saved as f64 / 1024.0,
+                ));
+            }
+            // sizes before and after the pass under origin and minify, time under brotli.
+            for pass in &f.passes {
+                println(format!(
+                    "{1:>0$}: {2:7.02}KiB {3:7.02}KiB {4:8.0}ms",
+                    file_name_max_len,
+                    format!("<{}>", pass.name),
+                    pass.bytes_in as f64 / 1024.0,
+                    pass.bytes_out as f64 / 1024.0,
+                    pass.millis,
+                ));
+            }
+        }
+        print_row("total", &self.total(), baseline.map(|b| b.total()).as_ref());
+    }
+}
+
+/// Size budgets checked against brotli-compressed sizes.
+///
+/// Written as `<pattern>=<size>` pairs separated by `;`, e.g. `*.wasm=150KiB;*.js=10KiB;total=200KiB`.
+/// `*` in a pattern matches any characters and `total` limits the sum of all files.
+#[derive(Debug, Default)]
+pub struct Budget {
+    files: Vec<(String, usize)>,
+    total: Option<usize>,
+}
+
+impl FromStr for Budget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut budget = Budget::default();
+        for entry in s.split(';').map(str::trim).filter(|x| !x.is_empty()) {
+            let Some((pattern, size)) = entry.split_once('=') else {
+                bail!("budget entry should be `<pattern>=<size>`: {entry}");
+            };
+            let size = parse_size(size)?;
+            match pattern.trim() {
+                "total" => budget.total = Some(size),
+                pattern => budget.files.push((pattern.to_owned(), size)),
+            }
+        }
+        Ok(budget)
+    }
+}
+
+impl Budget {
+    /// returns human readable descriptions of every exceeded budget.
+    pub fn check(&self, report: &Report) -> Vec<String> {
+        let mut violations = vec![];
+        for f in &report.files {
+            let name = stable_name(&f.file_name);
+            for (pattern, limit) in &self.files {
+                if glob_match(pattern, &name) && f.stats.brotlied_size > *limit {
+                    violations.push(format!(
+                        "{} is {} bytes (budget for `{pattern}` is {limit} bytes)",
+                        f.file_name, f.stats.brotlied_size,
+                    ));
+                }
+            }
+        }
+        if let Some(limit) = self.total {
+            let total = report.total().brotlied_size;
+            if total > limit {
+                violations.push(format!("total is {total} bytes (budget is {limit} bytes)"));
+            }
+        }
+        violations
+    }
+}
+
+fn parse_size(s: &str) -> Result<usize> {
+    let s = s.trim();
+    let (num, unit) = s.split_at(
+        s.find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(s.len()),
+    );
+    let num = num
+        .parse::<f64>()
+        .with_context(|| format!("invalid size: {s}"))?;
+    let unit = match unit.trim() {
+        "" | "B" => 1,
+        "KiB" => 1024,
+        "MiB" => 1024 * 1024,
+        unit => bail!("unknown size unit: {unit}"),
+    };
+    Ok((num * unit as f64) as usize)
+}
+
+// quoted as RFC 4180 asks when it has a comma, a quote or a line break.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+pub fn glob_match(pattern: &str, s: &str) -> bool {
+    let Some((prefix, rest)) = pattern.split_once('*') else {
+        return pattern == s;
+    };
+    let Some(s) = s.strip_prefix(prefix) else { return false };
+    (0..=s.len())
+        .filter(|&i| s.is_char_boundary(i))
+        .any(|i| glob_match(rest, &s[i..]))
+}
+
+// trunk appends content hash to file names (e.g. `index-1a2b3c4d5e6f7a8b.css`)
+fn stable_name(file_name: &str) -> String {
+    let mut ret = String::new();
+    let mut rest = file_name;
+    while let Some(pos) = rest.find('-') {
+        ret.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        let hash_len = after.bytes().take_while(u8::is_ascii_hexdigit).count();
+        let terminated = matches!(after.as_bytes().get(hash_len), None | Some(b'.' | b'_'));
+        if (8..=16).contains(&hash_len) && terminated {
+            rest = &after[hash_len..];
+        } else {
+            ret.push('-');
+            rest = after;
+        }
+    }
+    ret.push_str(rest);
+    ret
+}
+
+#[test]
+fn budget() {
+    assert_eq!(
+        stable_name("shoutter_web-1a2b3c4d5e6f7a8b_bg.wasm"),
+        "shoutter_web_bg.wasm"
+    );
+    assert_eq!(stable_name("index-deadbeef.css"), "index.css");
+    assert_eq!(stable_name("some-file.js"), "some-file.js");
+
+    let budget = "*.wasm=1.5KiB; total=2KiB".parse::<Budget>().unwrap();
+    assert_eq!(budget.files, vec![("*.wasm".to_owned(), 1536)]);
+    assert_eq!(budget.total, Some(2048));
+    assert!(glob_match("*_bg.wasm", "shoutter_web_bg.wasm"));
+    assert!(!glob_match("*.js", "shoutter_web_bg.wasm"));
+}
+
+#[test]
+fn csv() {
+    let file = |file_name: &str| FileReport {
+        file_name: file_name.to_owned(),
+        stats: ProcessStats {
+            origin_size: 3,
+            minified_size: None,
+            brotlied_size: 1,
+        },
+        sections: vec![],
+        passes: vec![],
+    };
+    let report = Report {
+        files: vec![file("index.css"), file(r#"a,"b".js"#)],
+    };
+    assert_eq!(
+        report.to_csv(),
+        "file_name,origin_size,minified_size,brotlied_size\nindex.css,3,,1\n\"a,\"\"b\"\".js\",3,,1\n"
+    );
+}
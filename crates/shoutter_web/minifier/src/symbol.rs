@@ -1,40 +1,48 @@
 #![allow(dead_code)]
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::Hash;
 
+use anyhow::{bail, ensure, Context, Result};
 use minifier_rs_macro::struct_map;
-use wasm_encoder::{ConstExpr, ElementSegment};
+use wasm_encoder::{ConstExpr, ElementSegment, Encode};
 
+use crate::report::{glob_match, SectionSaving};
+use crate::{opt_data, opt_wasm, verify};
+
+// expands to a single `match`, so a variant added to wasmparser fails to compile
+// instead of panicking at runtime.
 macro_rules! enum_map {
     () => {};
     (fn $fn_name:ident($ty:ident) { $($tt:tt)* } $($tail:tt)*) => {
-        fn $fn_name(a: wasmparser::$ty) -> wasm_encoder::$ty {
-            enum_map!(@arm [a $ty $ty] $($tt)*);
-            unreachable!();
+        pub fn $fn_name(a: wasmparser::$ty) -> wasm_encoder::$ty {
+            enum_map!(@arm [a $ty $ty] [] $($tt)*)
         }
         enum_map!($($tail)*);
     };
     (fn $fn_name:ident($from_ty:ident) -> $to_ty:ident { $($tt:tt)* } $($tail:tt)*) => {
-        fn $fn_name(a: wasmparser::$from_ty) -> wasm_encoder::$to_ty {
-            enum_map!(@arm [a $from_ty $to_ty] $($tt)*);
-            unreachable!();
+        pub fn $fn_name(a: wasmparser::$from_ty) -> wasm_encoder::$to_ty {
+            enum_map!(@arm [a $from_ty $to_ty] [] $($tt)*)
         }
         enum_map!($($tail)*);
     };
 
-    (@arm [$var:ident $from_ty:ident $to_ty:ident]) => {};
-    (@arm [$var:ident $from_ty:ident $to_ty:ident] $name:ident$(($($params:ident),*$(,)?))? => $block:block, $($tail:tt)*) => {
-        if let wasmparser::$from_ty::$name$(($($params)*))? = $var {
-            return $block;
+    (@arm [$var:ident $from_ty:ident $to_ty:ident] [$($arms:tt)*]) => {
+        match $var {
+            $($arms)*
         }
-        enum_map!(@arm [$var $from_ty $to_ty] $($tail)*);
     };
-    (@arm [$var:ident $from_ty:ident $to_ty:ident] $name:ident$(($($params:ident),*$(,)?))?, $($tail:tt)*) => {
-        #[allow(irrefutable_let_patterns)]
-        if let wasmparser::$from_ty::$name$(($($params)*))? = $var {
-            return wasm_encoder::$to_ty::$name$(($($params)*))?;
-        }
-        enum_map!(@arm [$var $from_ty $to_ty] $($tail)*);
+    (@arm [$var:ident $from_ty:ident $to_ty:ident] [$($arms:tt)*] $name:ident$(($($params:ident),*$(,)?))? => $block:block, $($tail:tt)*) => {
+        enum_map!(@arm [$var $from_ty $to_ty] [
+            $($arms)*
+            wasmparser::$from_ty::$name$(($($params),*))? => $block,
+        ] $($tail)*)
+    };
+    (@arm [$var:ident $from_ty:ident $to_ty:ident] [$($arms:tt)*] $name:ident$(($($params:ident),*$(,)?))?, $($tail:tt)*) => {
+        enum_map!(@arm [$var $from_ty $to_ty] [
+            $($arms)*
+            wasmparser::$from_ty::$name$(($($params),*))? => wasm_encoder::$to_ty::$name$(($($params),*))?,
+        ] $($tail)*)
     };
 }
 
@@ -80,33 +88,41 @@ struct_map! {
     }
 }
 
-fn map_const_expr(c: wasmparser::ConstExpr) -> wasm_encoder::ConstExpr {
+fn map_const_expr(c: wasmparser::ConstExpr) -> Result<wasm_encoder::ConstExpr> {
     let mut reader = c.get_binary_reader();
     // wasm_encoder::ConstExpr appends Instruction::End at last
-    let bytes = reader.read_bytes(reader.bytes_remaining() - 1).unwrap();
-    wasm_encoder::ConstExpr::raw(bytes.iter().copied())
+    let len = reader
+        .bytes_remaining()
+        .checked_sub(1)
+        .context("const expr is empty")?;
+    let bytes = reader.read_bytes(len)?;
+    Ok(wasm_encoder::ConstExpr::raw(bytes.iter().copied()))
 }
 fn map_element_items<'a>(
     items: wasmparser::ElementItems,
     functions: &'a mut Vec<u32>,
     const_exprs: &'a mut Vec<wasm_encoder::ConstExpr>,
-) -> wasm_encoder::Elements<'a> {
-    match items {
+) -> Result<wasm_encoder::Elements<'a>> {
+    Ok(match items {
         wasmparser::ElementItems::Functions(f) => {
-            functions.extend(f.into_iter().map(|x| x.unwrap()));
+            for function in f {
+                functions.push(function?);
+            }
             wasm_encoder::Elements::Functions(functions)
         }
         wasmparser::ElementItems::Expressions(e) => {
-            const_exprs.extend(e.into_iter().map(|x| map_const_expr(x.unwrap())));
+            for expr in e {
+                const_exprs.push(map_const_expr(expr?)?);
+            }
             wasm_encoder::Elements::Expressions(const_exprs)
         }
-    }
+    })
 }
 fn map_element_kind<'a>(
     e: wasmparser::ElementKind,
     offset: &'a mut Option<ConstExpr>, // just for storage. should be None
-) -> wasm_encoder::ElementMode<'a> {
-    match e {
+) -> Result<wasm_encoder::ElementMode<'a>> {
+    Ok(match e {
         wasmparser::ElementKind::Passive => wasm_encoder::ElementMode::Passive,
         wasmparser::ElementKind::Active {
             table_index,
@@ -114,12 +130,12 @@ fn map_element_kind<'a>(
         } => wasm_encoder::ElementMode::Active {
             table: table_index,
             offset: {
-                offset.replace(map_const_expr(offset_expr));
+                offset.replace(map_const_expr(offset_expr)?);
                 offset.as_ref().unwrap()
             },
         },
         wasmparser::ElementKind::Declared => wasm_encoder::ElementMode::Declared,
-    }
+    })
 }
 fn map_ref_type(ref_: wasmparser::RefType) -> wasm_encoder::RefType {
     wasm_encoder::RefType {
@@ -128,32 +144,188 @@ fn map_ref_type(ref_: wasmparser::RefType) -> wasm_encoder::RefType {
     }
 }
 
-pub async fn minify_symbol(wasm: &mut Vec<u8>, js: &mut Vec<u8>) {
+/// Original to minified names of the wasm imports and exports.
+#[derive(Default)]
+pub struct SymbolMap {
+    /// module => (minified module, name => minified name)
+    pub imports: HashMap<String, (String, HashMap<String, String>)>,
+    pub exports: HashMap<String, String>,
+    /// imports never called from the module. they share a single name.
+    pub unused_imports: HashSet<(String, String)>,
+}
+
+/// Result of `minify_symbol` besides the rewritten module.
+pub struct Minified {
+    pub symbols: SymbolMap,
+    pub sections: Vec<SectionSaving>,
+    /// placeholder => original text of stripped panic messages and locations
+    pub panics: BTreeMap<String, String>,
+}
+
+/// How many times each wasm symbol is referenced from the JS glue (see `opt_js`).
+#[derive(Default)]
+pub struct SymbolUsage {
+    pub modules: HashMap<String, usize>,
+    pub imports: HashMap<(String, String), usize>,
+    pub exports: HashMap<String, usize>,
+    /// `wasm` is passed around as a whole, so any export may be used.
+    pub escaped: bool,
+    /// `wasm` is returned to callers of wasm-bindgen's `init`, who may use any export
+    /// unless `SymbolOptions::used_exports` lists the ones they do.
+    pub returned: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct SymbolOptions {
+    /// custom sections (`name`, `producers`, `.debug_*`, ...) to strip.
+    /// `*` matches any characters. nothing is stripped by default.
+    pub strip_sections: Vec<String>,
+    /// custom sections to keep even if they match `strip_sections`.
+    pub keep_sections: Vec<String>,
+    /// rename exported functions in the `name` section to their minified export names.
+    pub rename_names: bool,
+    /// exports keeping their original names.
+    pub pinned_exports: Vec<String>,
+    /// exports used outside the glue, e.g. by callers of wasm-bindgen's `init`, which
+    /// gets every export. listing any turns on dropping the others unused by the glue.
+    pub used_exports: Vec<String>,
+    /// keep exports and imports unused by the glue.
+    pub keep_unused: bool,
+    /// optimize function bodies (see `opt_wasm`).
+    pub optimize_code: bool,
+    /// merge data segments and leave out zeros (see `opt_data`).
+    pub compact_data: bool,
+    /// point identical strings in data to a single copy. implies `compact_data`.
+    /// every `i32.const` equal to the address of a duplicate is rewritten, even where
+    /// it is not a pointer, so this stays opt-in.
+    pub dedup_data: bool,
+    /// replace panic messages and locations in data with placeholders (see `opt_panic`).
+    /// implies `compact_data`.
+    pub strip_panics: bool,
+}
+
+/// Shortens import and export names of the wasm module.
+/// The wasm-bindgen glue has to be patched with the returned map (see `opt_js`).
+///
+/// With `usage` of the glue, the most referenced symbols get the shortest names,
+/// exports never referenced are dropped and imports never called share a single name
+/// so that their shims can be removed from the glue.
+pub fn minify_symbol(
+    wasm: &mut Vec<u8>,
+    usage: Option<&SymbolUsage>,
+    options: &SymbolOptions,
+) -> Result<Minified> {
     let parser = wasmparser::Parser::new(0);
 
-    let mut module = wasm_encoder::Module::new();
-    let mut imports_ident_map = HashMap::new();
-    let mut exports_ident_map = HashMap::new();
+    let symbols = Symbols::collect(wasm)?;
+    let eliminate = usage.is_some() && !options.keep_unused;
+    let eliminate_exports =
+        eliminate && usage.map_or(false, |u| !u.returned || !options.used_exports.is_empty());
+    let pinned = options
+        .pinned_exports
+        .iter()
+        .map(|x| x.as_str())
+        .collect::<HashSet<_>>();
+
+    let modules_ident_map = assign_idents(
+        symbols.modules,
+        |x| usage.and_then(|u| u.modules.get(x)).copied().unwrap_or(0),
+        &HashSet::new(),
+    );
 
-    let mut module_ident = MinifiedIdent::new();
-    let mut name_ident = MinifiedIdent::new();
-    let mut export_ident = MinifiedIdent::new();
+    // `None` stands for the shared name of unused imports
+    let mut unused_imports = HashSet::new();
+    let names = symbols
+        .names
+        .into_iter()
+        .map(|x| {
+            if eliminate && symbols.uncalled.contains(&x) {
+                unused_imports.insert(x);
+                None
+            } else {
+                Some(x)
+            }
+        })
+        .collect::<Vec<_>>();
+    let names_ident_map = assign_idents(
+        names,
+        |x| match (x, usage) {
+            (Some(x), Some(u)) => u.imports.get(x).copied().unwrap_or(0),
+            _ => 0,
+        },
+        &HashSet::new(),
+    );
+    let mut imports_ident_map = HashMap::<_, (_, HashMap<_, _>)>::new();
+    for (name, ident) in &names_ident_map {
+        let Some((module, name)) = name else { continue };
+        imports_ident_map
+            .entry(module.clone())
+            .or_insert_with(|| (modules_ident_map[module].clone(), HashMap::new()))
+            .1
+            .insert(name.clone(), ident.clone());
+    }
+    for (module, name) in &unused_imports {
+        imports_ident_map
+            .entry(module.clone())
+            .or_insert_with(|| (modules_ident_map[module].clone(), HashMap::new()))
+            .1
+            .insert(name.clone(), names_ident_map[&None].clone());
+    }
+
+    let (pinned_exports, exports) = symbols
+        .exports
+        .into_iter()
+        .filter(|x| {
+            !eliminate_exports
+                || pinned.contains(x.as_str())
+                || options.used_exports.contains(x)
+                || usage.map_or(false, |u| u.escaped || u.exports.contains_key(x))
+        })
+        .partition::<Vec<_>, _>(|x| pinned.contains(x.as_str()));
+    let mut exports_ident_map = assign_idents(
+        exports,
+        |x| usage.and_then(|u| u.exports.get(x)).copied().unwrap_or(0),
+        &pinned,
+    );
+    exports_ident_map.extend(pinned_exports.into_iter().map(|x| (x.clone(), x)));
 
+    let mut module = wasm_encoder::Module::new();
+    // function index => minified export name
+    let mut function_exports = HashMap::new();
+    let mut savings = vec![];
+    let data_plan = if options.compact_data || options.dedup_data || options.strip_panics {
+        opt_data::plan(wasm, options.dedup_data, options.strip_panics)
+            .context("failed to plan data segments")?
+    } else {
+        None
+    };
+    let (consts, lens) = data_plan
+        .as_ref()
+        .map(|x| (x.consts.clone(), x.lens.clone()))
+        .unwrap_or_default();
+
+    // param counts of types and type indices of defined functions, to tell locals from params
+    let mut type_param_counts = vec![];
+    let mut function_types = vec![];
     let mut code_section_remaining = 0;
+    let mut code_section_count = 0;
     let mut code_section_encoder = None;
 
     for payload in parser.parse_all(wasm) {
-        let payload = payload.unwrap();
-        match payload {
+        match payload? {
             wasmparser::Payload::TypeSection(section) => {
                 let mut encoder = wasm_encoder::TypeSection::new();
                 for ty in section {
-                    match ty.unwrap() {
-                        wasmparser::Type::Func(f) => encoder.function(
-                            f.params().iter().copied().map(map_val_type),
-                            f.results().iter().copied().map(map_val_type),
-                        ),
+                    match ty? {
+                        wasmparser::Type::Func(f) => {
+                            type_param_counts.push(f.params().len() as u32);
+                            encoder.function(
+                                f.params().iter().copied().map(map_val_type),
+                                f.results().iter().copied().map(map_val_type),
+                            )
+                        }
                         wasmparser::Type::Array(a) => {
+                            type_param_counts.push(0);
                             encoder.array(map_storage_type(a.element_type), a.mutable)
                         }
                     };
@@ -163,13 +335,9 @@ pub async fn minify_symbol(wasm: &mut Vec<u8>, js: &mut Vec<u8>) {
             wasmparser::Payload::ImportSection(section) => {
                 let mut encoder = wasm_encoder::ImportSection::new();
                 for import in section {
-                    let import = import.unwrap();
-                    let (module_name, name_map) = imports_ident_map
-                        .entry(import.module)
-                        .or_insert_with(|| (module_ident.next().unwrap(), HashMap::new()));
-                    let name = name_map
-                        .entry(import.name)
-                        .or_insert_with(|| name_ident.next().unwrap());
+                    let import = import?;
+                    let (module_name, name_map) = &imports_ident_map[import.module];
+                    let name = &name_map[import.name];
                     encoder.import(
                         module_name,
                         name,
@@ -195,38 +363,49 @@ pub async fn minify_symbol(wasm: &mut Vec<u8>, js: &mut Vec<u8>) {
             wasmparser::Payload::FunctionSection(section) => {
                 let mut encoder = wasm_encoder::FunctionSection::new();
                 for function in section {
-                    encoder.function(function.unwrap());
+                    let function = function?;
+                    function_types.push(function);
+                    encoder.function(function);
                 }
                 module.section(&encoder);
             }
             wasmparser::Payload::TableSection(section) => {
                 let mut encoder = wasm_encoder::TableSection::new();
                 for table in section {
-                    encoder.table(map_table_type(table.unwrap().ty));
+                    let table = table?;
+                    match table.init {
+                        wasmparser::TableInit::RefNull => {
+                            encoder.table(map_table_type(table.ty));
+                        }
+                        wasmparser::TableInit::Expr(init) => {
+                            encoder
+                                .table_with_init(map_table_type(table.ty), &map_const_expr(init)?);
+                        }
+                    }
                 }
                 module.section(&encoder);
             }
             wasmparser::Payload::MemorySection(section) => {
                 let mut encoder = wasm_encoder::MemorySection::new();
                 for memory in section {
-                    encoder.memory(map_memory_type(memory.unwrap()));
+                    encoder.memory(map_memory_type(memory?));
                 }
                 module.section(&encoder);
             }
             wasmparser::Payload::TagSection(section) => {
                 let mut encoder = wasm_encoder::TagSection::new();
                 for tag in section {
-                    encoder.tag(map_tag_type(tag.unwrap()));
+                    encoder.tag(map_tag_type(tag?));
                 }
                 module.section(&encoder);
             }
             wasmparser::Payload::GlobalSection(section) => {
                 let mut encoder = wasm_encoder::GlobalSection::new();
                 for global in section {
-                    let global = global.unwrap();
+                    let global = global?;
                     encoder.global(
                         map_global_type(global.ty),
-                        &map_const_expr(global.init_expr),
+                        &map_const_expr(global.init_expr)?,
                     );
                 }
                 module.section(&encoder);
@@ -234,10 +413,16 @@ pub async fn minify_symbol(wasm: &mut Vec<u8>, js: &mut Vec<u8>) {
             wasmparser::Payload::ExportSection(section) => {
                 let mut encoder = wasm_encoder::ExportSection::new();
                 for export in section {
-                    let export = export.unwrap();
-                    let export_name = exports_ident_map
-                        .entry(export.name)
-                        .or_insert_with(|| export_ident.next().unwrap());
+                    let export = export?;
+                    // dropped as unused
+                    let Some(export_name) = exports_ident_map.get(export.name) else {
+                        continue;
+                    };
+                    if export.kind == wasmparser::ExternalKind::Func {
+                        function_exports
+                            .entry(export.index)
+                            .or_insert_with(|| export_name.clone());
+                    }
                     encoder.export(export_name, map_external_kind(export.kind), export.index);
                 }
                 module.section(&encoder);
@@ -246,26 +431,34 @@ pub async fn minify_symbol(wasm: &mut Vec<u8>, js: &mut Vec<u8>) {
             wasmparser::Payload::ElementSection(section) => {
                 let mut encoder = wasm_encoder::ElementSection::new();
                 for element in section {
-                    let element = element.unwrap();
+                    let element = element?;
                     let (mut offset, mut functions, mut const_exprs) = (None, vec![], vec![]);
                     let segment = ElementSegment {
-                        mode: map_element_kind(element.kind, &mut offset),
+                        mode: map_element_kind(element.kind, &mut offset)?,
                         element_type: map_ref_type(element.ty),
                         elements: map_element_items(
                             element.items,
                             &mut functions,
                             &mut const_exprs,
-                        ),
+                        )?,
                     };
                     encoder.segment(segment);
                 }
                 module.section(&encoder);
             }
 
+            wasmparser::Payload::DataSection(_) if data_plan.is_some() => {
+                let plan = data_plan.as_ref().unwrap();
+                module.section(&plan.encode());
+                savings.push(SectionSaving {
+                    name: "data".to_owned(),
+                    saved: plan.saved,
+                });
+            }
             wasmparser::Payload::DataSection(section) => {
                 let mut encoder = wasm_encoder::DataSection::new();
                 for data in section {
-                    let data = data.unwrap();
+                    let data = data?;
                     match data.kind {
                         wasmparser::DataKind::Passive => {
                             encoder.passive(data.data.iter().copied());
@@ -276,7 +469,7 @@ pub async fn minify_symbol(wasm: &mut Vec<u8>, js: &mut Vec<u8>) {
                         } => {
                             encoder.active(
                                 memory_index,
-                                &map_const_expr(offset_expr),
+                                &map_const_expr(offset_expr)?,
                                 data.data.iter().copied(),
                             );
                         }
@@ -285,38 +478,77 @@ pub async fn minify_symbol(wasm: &mut Vec<u8>, js: &mut Vec<u8>) {
                 module.section(&encoder);
             }
 
+            wasmparser::Payload::StartSection { func, .. } => {
+                module.section(&wasm_encoder::StartSection {
+                    function_index: func,
+                });
+            }
+            wasmparser::Payload::DataCountSection { count, .. } => {
+                let count = data_plan
+                    .as_ref()
+                    .map_or(count, |x| x.segments.len() as u32);
+                module.section(&wasm_encoder::DataCountSection { count });
+            }
+
             wasmparser::Payload::CustomSection(section) => {
+                let name = section.name();
+                let matches = |globs: &[String]| globs.iter().any(|x| glob_match(x, name));
+                if matches(&options.strip_sections) && !matches(&options.keep_sections) {
+                    savings.push(SectionSaving {
+                        name: name.to_owned(),
+                        saved: custom_section_size(name, section.data()) as i64,
+                    });
+                    continue;
+                }
+                let data = if name == "name" && options.rename_names {
+                    let data =
+                        rename_functions(section.data(), section.data_offset(), &function_exports)
+                            .context("failed to rewrite the name section")?;
+                    savings.push(SectionSaving {
+                        name: name.to_owned(),
+                        saved: custom_section_size(name, section.data()) as i64
+                            - custom_section_size(name, &data) as i64,
+                    });
+                    data.into()
+                } else {
+                    section.data().into()
+                };
                 module.section(&wasm_encoder::CustomSection {
-                    name: section.name().into(),
-                    data: section.data().into(),
+                    name: name.into(),
+                    data,
                 });
             }
 
             wasmparser::Payload::CodeSectionStart { count, .. } => {
-                assert_eq!(code_section_remaining, 0);
+                ensure!(code_section_remaining == 0, "nested code section");
                 code_section_remaining = count;
-                code_section_encoder = Some(wasm_encoder::CodeSection::new());
-            }
-
-            wasmparser::Payload::CodeSectionEntry(f) => {
-                let mut reader = f.get_binary_reader();
-                let bytes = reader.read_bytes(reader.bytes_remaining()).unwrap();
-
-                let mut function = wasm_encoder::Function::new([]);
-
-                pub struct Function {
-                    bytes: Vec<u8>,
+                code_section_count = count as usize;
+                if count == 0 {
+                    module.section(&wasm_encoder::CodeSection::new());
+                } else {
+                    code_section_encoder = Some(wasm_encoder::CodeSection::new());
                 }
-                unsafe {
-                    (*(&function as *const _ as *const Function as *mut Function))
-                        .bytes
-                        .clear();
-                }
-                assert_eq!(function.byte_len(), 0);
+            }
 
-                function.raw(bytes.iter().copied());
+            wasmparser::Payload::CodeSectionEntry(body) => {
+                let index = code_section_count - code_section_remaining as usize;
+                let param_count = function_types
+                    .get(index)
+                    .and_then(|ty| type_param_counts.get(*ty as usize))
+                    .copied()
+                    .context("function without a type")?;
+                let function = opt_wasm::rewrite_function(
+                    &body,
+                    param_count,
+                    options.optimize_code,
+                    &consts,
+                    &lens,
+                )
+                .with_context(|| format!("failed to rewrite function {index}"))?;
 
-                let encoder = code_section_encoder.as_mut().unwrap();
+                let encoder = code_section_encoder
+                    .as_mut()
+                    .context("code section entry outside of code section")?;
                 encoder.function(&function);
 
                 code_section_remaining -= 1;
@@ -328,12 +560,14 @@ pub async fn minify_symbol(wasm: &mut Vec<u8>, js: &mut Vec<u8>) {
 
             wasmparser::Payload::Version { .. } | wasmparser::Payload::End(_) => {}
 
-            e @ (wasmparser::Payload::StartSection { .. }
+            // sections from future proposals are kept as they are
+            wasmparser::Payload::UnknownSection { id, contents, .. } => {
+                module.section(&wasm_encoder::RawSection { id, data: contents });
+            }
+
+            wasmparser::Payload::ModuleSection { .. }
             | wasmparser::Payload::InstanceSection(_)
             | wasmparser::Payload::CoreTypeSection(_)
-            | wasmparser::Payload::UnknownSection { .. }
-            | wasmparser::Payload::DataCountSection { .. }
-            | wasmparser::Payload::ModuleSection { .. }
             | wasmparser::Payload::ComponentSection { .. }
             | wasmparser::Payload::ComponentInstanceSection(_)
             | wasmparser::Payload::ComponentAliasSection(_)
@@ -341,38 +575,265 @@ pub async fn minify_symbol(wasm: &mut Vec<u8>, js: &mut Vec<u8>) {
             | wasmparser::Payload::ComponentCanonicalSection(_)
             | wasmparser::Payload::ComponentStartSection { .. }
             | wasmparser::Payload::ComponentImportSection(_)
-            | wasmparser::Payload::ComponentExportSection(_)) => todo!("{e:#?}"),
+            | wasmparser::Payload::ComponentExportSection(_) => {
+                bail!("wasm components are not supported, only core modules are")
+            }
+        }
+    }
+
+    ensure!(code_section_encoder.is_none(), "code section is truncated");
+
+    let minified = module.finish();
+    // operands pointing into data are rewritten along with it
+    let same_code = !options.optimize_code && consts.is_empty() && lens.is_empty();
+    verify::verify(wasm, &minified, same_code, data_plan.is_none())
+        .context("rewritten wasm module failed verification")?;
+    *wasm = minified;
+    let symbols = SymbolMap {
+        imports: imports_ident_map,
+        exports: exports_ident_map,
+        unused_imports,
+    };
+    Ok(Minified {
+        symbols,
+        sections: savings,
+        panics: data_plan.map(|x| x.panics).unwrap_or_default(),
+    })
+}
+
+// symbols of the module in the order of appearance.
+#[derive(Default)]
+struct Symbols {
+    modules: Vec<String>,
+    names: Vec<(String, String)>,
+    exports: Vec<String>,
+    /// imported functions not referenced from anywhere in the module.
+    uncalled: HashSet<(String, String)>,
+}
+
+impl Symbols {
+    fn collect(wasm: &[u8]) -> Result<Symbols> {
+        let mut symbols = Symbols::default();
+        let mut imported_functions = vec![];
+        let mut referenced = HashSet::new();
+
+        for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+            match payload? {
+                wasmparser::Payload::ImportSection(section) => {
+                    for import in section {
+                        let import = import?;
+                        let name = (import.module.to_owned(), import.name.to_owned());
+                        if let wasmparser::TypeRef::Func(_) = import.ty {
+                            imported_functions.push(name.clone());
+                        }
+                        symbols.modules.push(import.module.to_owned());
+                        symbols.names.push(name);
+                    }
+                }
+                wasmparser::Payload::TableSection(section) => {
+                    for table in section {
+                        if let wasmparser::TableInit::Expr(init) = table?.init {
+                            const_expr_functions(init, &mut referenced)?;
+                        }
+                    }
+                }
+                wasmparser::Payload::GlobalSection(section) => {
+                    for global in section {
+                        const_expr_functions(global?.init_expr, &mut referenced)?;
+                    }
+                }
+                wasmparser::Payload::ExportSection(section) => {
+                    for export in section {
+                        let export = export?;
+                        if export.kind == wasmparser::ExternalKind::Func {
+                            referenced.insert(export.index);
+                        }
+                        symbols.exports.push(export.name.to_owned());
+                    }
+                }
+                wasmparser::Payload::StartSection { func, .. } => {
+                    referenced.insert(func);
+                }
+                wasmparser::Payload::ElementSection(section) => {
+                    for element in section {
+                        match element?.items {
+                            wasmparser::ElementItems::Functions(f) => {
+                                for function in f {
+                                    referenced.insert(function?);
+                                }
+                            }
+                            wasmparser::ElementItems::Expressions(e) => {
+                                for expr in e {
+                                    const_expr_functions(expr?, &mut referenced)?;
+                                }
+                            }
+                        }
+                    }
+                }
+                wasmparser::Payload::CodeSectionEntry(body) => {
+                    for op in body.get_operators_reader()? {
+                        match op? {
+                            wasmparser::Operator::Call { function_index }
+                            | wasmparser::Operator::ReturnCall { function_index }
+                            | wasmparser::Operator::RefFunc { function_index } => {
+                                referenced.insert(function_index);
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
         }
+
+        // the same function may be imported more than once
+        let called = imported_functions
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| referenced.contains(&(*i as u32)))
+            .map(|(_, name)| name)
+            .collect::<HashSet<_>>();
+        symbols.uncalled = imported_functions
+            .iter()
+            .filter(|x| !called.contains(x))
+            .cloned()
+            .collect();
+        Ok(symbols)
     }
+}
 
-    assert!(code_section_encoder.is_none());
+// as encoded: section id, size, name length and name included.
+fn custom_section_size(name: &str, data: &[u8]) -> usize {
+    let mut encoded = vec![];
+    wasm_encoder::CustomSection {
+        name: name.into(),
+        data: data.into(),
+    }
+    .encode(&mut encoded);
+    1 + encoded.len()
+}
 
-    let new_wasm = module.finish();
-    let mut js_string = String::from_utf8(js.clone()).unwrap();
+fn const_expr_functions(expr: wasmparser::ConstExpr, referenced: &mut HashSet<u32>) -> Result<()> {
+    for op in expr.get_operators_reader() {
+        if let wasmparser::Operator::RefFunc { function_index } = op? {
+            referenced.insert(function_index);
+        }
+    }
+    Ok(())
+}
 
-    // drawback: modifing javascript AST is better
-    for (mod_before, (mod_after, fn_idents)) in imports_ident_map {
-        js_string = js_string.replace(
-            &format!("imports.{mod_before} = {{}};"),
-            &format!("imports.{mod_after} = {{}};"),
-        );
+// rewrites the function names subsection and copies the others as they are.
+fn rename_functions(data: &[u8], offset: usize, names: &HashMap<u32, String>) -> Result<Vec<u8>> {
+    const FUNCTION_NAMES: u8 = 1;
 
-        for (fn_before, fn_after) in fn_idents {
-            js_string = js_string.replace(
-                &format!("imports.{mod_before}.{fn_before}"),
-                &format!("imports.{mod_after}.{fn_after}"),
+    let mut reader = wasmparser::BinaryReader::new_with_offset(data, offset);
+    let mut ret = vec![];
+    while !reader.eof() {
+        let id = reader.read_u8()?;
+        let len = reader.read_var_u32()? as usize;
+        let content_offset = reader.original_position();
+        let content = reader.read_bytes(len)?;
+        ret.push(id);
+        if id != FUNCTION_NAMES {
+            len.encode(&mut ret);
+            ret.extend_from_slice(content);
+            continue;
+        }
+        let mut map = wasm_encoder::NameMap::new();
+        for naming in wasmparser::NameMap::new(content, content_offset)? {
+            let naming = naming?;
+            map.append(
+                naming.index,
+                names.get(&naming.index).map_or(naming.name, |x| x),
             );
         }
+        let mut encoded = vec![];
+        map.encode(&mut encoded);
+        encoded.len().encode(&mut ret);
+        ret.extend(encoded);
     }
-    for (export_before, export_after) in exports_ident_map {
-        js_string = js_string.replace(
-            &format!("wasm.{export_before}"),
-            &format!("wasm.{export_after}"),
-        );
+    Ok(ret)
+}
+
+// the most used symbol gets the shortest identifier. ties are broken by the order in the module.
+fn assign_idents<K: Clone + Eq + Hash>(
+    symbols: Vec<K>,
+    usage: impl Fn(&K) -> usize,
+    reserved: &HashSet<&str>,
+) -> HashMap<K, String> {
+    let mut unique = vec![];
+    for symbol in symbols {
+        if !unique.contains(&symbol) {
+            unique.push(symbol);
+        }
     }
+    unique.sort_by_key(|x| std::cmp::Reverse(usage(x)));
+    let idents = MinifiedIdent::new().filter(|x| !reserved.contains(x.as_str()) && !is_keyword(x));
+    unique.into_iter().zip(idents).collect()
+}
 
-    *js = js_string.into_bytes();
-    *wasm = new_wasm;
+// reserved words of javascript, including the strict mode and contextual ones.
+fn is_keyword(ident: &str) -> bool {
+    matches!(
+        ident,
+        "arguments"
+            | "as"
+            | "async"
+            | "await"
+            | "break"
+            | "case"
+            | "catch"
+            | "class"
+            | "const"
+            | "continue"
+            | "debugger"
+            | "default"
+            | "delete"
+            | "do"
+            | "else"
+            | "enum"
+            | "eval"
+            | "export"
+            | "extends"
+            | "false"
+            | "finally"
+            | "for"
+            | "from"
+            | "function"
+            | "get"
+            | "if"
+            | "implements"
+            | "import"
+            | "in"
+            | "instanceof"
+            | "interface"
+            | "let"
+            | "new"
+            | "null"
+            | "of"
+            | "package"
+            | "private"
+            | "protected"
+            | "public"
+            | "return"
+            | "set"
+            | "static"
+            | "super"
+            | "switch"
+            | "this"
+            | "throw"
+            | "true"
+            | "try"
+            | "typeof"
+            | "undefined"
+            | "var"
+            | "void"
+            | "while"
+            | "with"
+            | "yield"
+            | "NaN"
+            | "Infinity"
+    )
 }
 
 struct MinifiedIdent {
@@ -411,3 +872,222 @@ fn minified_ident() {
         "a b c d e f g h i j k l m n o p q r s t u v w x y z A B C D E F G H I J K L M N O P Q R S T U V W X Y Z ba bb bc bd be bf bg bh",
     );
 }
+
+#[test]
+fn empty_const_expr() {
+    assert!(map_const_expr(wasmparser::ConstExpr::new(&[], 0)).is_err());
+}
+
+#[test]
+fn minify_symbol_passthrough() {
+    use wasm_encoder::{
+        CodeSection, DataCountSection, DataSection, EntityType, ExportKind, ExportSection,
+        Function, FunctionSection, ImportSection, Instruction, MemoryType, Module, RawSection,
+        StartSection, TypeSection,
+    };
+
+    // bulk memory module with a start function, as built with `-C target-feature=+bulk-memory`
+    let mut module = Module::new();
+    let mut types = TypeSection::new();
+    types.function([], []);
+    module.section(&types);
+    let mut imports = ImportSection::new();
+    let memory = MemoryType {
+        minimum: 1,
+        maximum: None,
+        memory64: false,
+        shared: false,
+    };
+    imports.import("env", "memory", EntityType::Memory(memory));
+    module.section(&imports);
+    let mut functions = FunctionSection::new();
+    functions.function(0);
+    module.section(&functions);
+    let mut exports = ExportSection::new();
+    exports.export("__wbindgen_start", ExportKind::Func, 0);
+    module.section(&exports);
+    module.section(&StartSection { function_index: 0 });
+    module.section(&DataCountSection { count: 1 });
+    let mut code = CodeSection::new();
+    let mut init = Function::new([]);
+    init.instruction(&Instruction::I32Const(0))
+        .instruction(&Instruction::I32Const(0))
+        .instruction(&Instruction::I32Const(4))
+        .instruction(&Instruction::MemoryInit {
+            mem: 0,
+            data_index: 0,
+        })
+        .instruction(&Instruction::DataDrop(0))
+        .instruction(&Instruction::End);
+    code.function(&init);
+    module.section(&code);
+    let mut data = DataSection::new();
+    data.passive(*b"wasm");
+    module.section(&data);
+    let original = module.finish();
+
+    let mut wasm = original.clone();
+    let symbols = minify_symbol(&mut wasm, None, &Default::default())
+        .unwrap()
+        .symbols;
+    assert_eq!(symbols.exports["__wbindgen_start"], "a");
+    assert!(wasm.len() < original.len());
+
+    // unknown sections are copied verbatim
+    let mut module = Module::new();
+    module.section(&RawSection {
+        id: 0x7f,
+        data: b"future",
+    });
+    let original = module.finish();
+    let mut wasm = original.clone();
+    minify_symbol(&mut wasm, None, &Default::default()).unwrap();
+    assert_eq!(wasm, original);
+}
+
+#[test]
+fn custom_sections() {
+    use wasm_encoder::{
+        CodeSection, CustomSection, ExportKind, ExportSection, Function, FunctionSection, Module,
+        NameMap, NameSection, TypeSection,
+    };
+
+    let mut module = Module::new();
+    let mut types = TypeSection::new();
+    types.function([], []);
+    module.section(&types);
+    let mut functions = FunctionSection::new();
+    functions.function(0).function(0);
+    module.section(&functions);
+    let mut exports = ExportSection::new();
+    exports.export("greet", ExportKind::Func, 1);
+    module.section(&exports);
+    let mut code = CodeSection::new();
+    code.function(&Function::new([]))
+        .function(&Function::new([]));
+    module.section(&code);
+    let mut names = NameSection::new();
+    let mut function_names = NameMap::new();
+    function_names.append(0, "shoutter_web::internal::h0123456789abcdef");
+    function_names.append(1, "greet");
+    names.functions(&function_names);
+    module.section(&names);
+    module.section(&CustomSection {
+        name: ".debug_info".into(),
+        data: [0; 100].as_slice().into(),
+    });
+    let original = module.finish();
+
+    let mut wasm = original.clone();
+    let options = SymbolOptions {
+        strip_sections: vec!["*".to_owned()],
+        keep_sections: vec!["name".to_owned()],
+        rename_names: true,
+        ..Default::default()
+    };
+    let savings = minify_symbol(&mut wasm, None, &options).unwrap().sections;
+    assert_eq!(savings.len(), 2);
+    assert_eq!((&*savings[0].name, savings[0].saved), ("name", 4));
+    assert_eq!((&*savings[1].name, savings[1].saved), (".debug_info", 114));
+
+    for payload in wasmparser::Parser::new(0).parse_all(&wasm) {
+        if let wasmparser::Payload::CustomSection(section) = payload.unwrap() {
+            assert_eq!(section.name(), "name");
+            let data = rename_functions(section.data(), 0, &HashMap::new()).unwrap();
+            assert_eq!(data, section.data());
+            assert!(!data.windows(5).any(|x| x == b"greet"));
+        }
+    }
+}
+
+#[test]
+fn assign_idents_by_usage() {
+    let symbols = ["memory", "greet", "__wbindgen_malloc", "greet"].map(str::to_owned);
+    let usage = HashMap::from([("__wbindgen_malloc".to_owned(), 5), ("greet".to_owned(), 1)]);
+    let idents = assign_idents(
+        symbols.to_vec(),
+        |x| usage.get(x).copied().unwrap_or(0),
+        &HashSet::from(["b"]),
+    );
+    assert_eq!(idents.len(), 3);
+    assert_eq!(idents["__wbindgen_malloc"], "a");
+    assert_eq!(idents["greet"], "c");
+    assert_eq!(idents["memory"], "d");
+
+    // two-letter identifiers are reached after 52 one-letter ones
+    let idents = MinifiedIdent::new()
+        .filter(|x| !is_keyword(x))
+        .take(1000)
+        .collect::<Vec<_>>();
+    assert!(!idents.iter().any(|x| x == "do" || x == "if" || x == "in"));
+}
+
+#[test]
+fn eliminate_unused_symbols() {
+    let original = crate::verify::wasm_bindgen_sample();
+    let usage = SymbolUsage {
+        exports: HashMap::from([("greet".to_owned(), 1)]),
+        ..Default::default()
+    };
+    let options = SymbolOptions {
+        pinned_exports: vec!["memory".to_owned()],
+        ..Default::default()
+    };
+
+    let mut wasm = original.clone();
+    let symbols = minify_symbol(&mut wasm, Some(&usage), &options)
+        .unwrap()
+        .symbols;
+    assert_eq!(symbols.exports.len(), 2);
+    assert_eq!(symbols.exports["memory"], "memory");
+    assert!(!symbols.exports.contains_key("__wbindgen_malloc"));
+    let unused = ("wbg".to_owned(), "__wbg_unused_9abc".to_owned());
+    assert_eq!(symbols.unused_imports, HashSet::from([unused]));
+
+    // everything is kept when `wasm` escapes from the glue
+    let usage = SymbolUsage {
+        escaped: true,
+        ..usage
+    };
+    let mut wasm = original.clone();
+    let symbols = minify_symbol(&mut wasm, Some(&usage), &options)
+        .unwrap()
+        .symbols;
+    assert_eq!(symbols.exports.len(), 3);
+
+    // callers of `init` get every export, unless the ones they use are given
+    let usage = SymbolUsage {
+        escaped: false,
+        returned: true,
+        ..usage
+    };
+    let mut wasm = original.clone();
+    let symbols = minify_symbol(&mut wasm, Some(&usage), &options)
+        .unwrap()
+        .symbols;
+    assert_eq!(symbols.exports.len(), 3);
+    let options = SymbolOptions {
+        used_exports: vec!["memory".to_owned()],
+        ..Default::default()
+    };
+    let mut wasm = original;
+    let symbols = minify_symbol(&mut wasm, Some(&usage), &options)
+        .unwrap()
+        .symbols;
+    assert_eq!(symbols.exports.len(), 2);
+    assert_ne!(symbols.exports["memory"], "memory");
+}
+
+#[test]
+fn rename_exports_of_glue() {
+    let usage = crate::opt_js::count_wasm_symbols(include_str!("opt_js/corpus/glue.js")).unwrap();
+    let mut wasm = crate::verify::wasm_bindgen_sample();
+    let symbols = minify_symbol(&mut wasm, Some(&usage), &Default::default())
+        .unwrap()
+        .symbols;
+    assert_eq!(symbols.exports.len(), 3);
+    assert!(symbols
+        .exports
+        .iter()
+        .all(|(name, minified)| name != minified));
+}
@@ -0,0 +1,9 @@
+use js_sys::Reflect;
+use wasm_bindgen::JsValue;
+
+// std::env is not available on wasm32-unknown-unknown, so read `process.env` directly.
+pub fn var(key: &str) -> Option<String> {
+    let process = Reflect::get(&js_sys::global(), &JsValue::from("process")).ok()?;
+    let env = Reflect::get(&process, &JsValue::from("env")).ok()?;
+    Reflect::get(&env, &JsValue::from(key)).ok()?.as_string()
+}
@@ -38,6 +38,32 @@ pub async fn mkdir(path: &Path) -> Result<()> {
     Ok(())
 }
 
+pub async fn mkdir_all(path: &Path) -> Result<()> {
+    #[wasm_bindgen(module = "fs/promises")]
+    extern "C" {
+        #[wasm_bindgen(catch)]
+        async fn mkdir(path: &str, options: &Object) -> Result<JsValue, JsValue>;
+    }
+
+    mkdir(path.to_str().unwrap(), &object! { recursive: true })
+        .await
+        .map_err(JsError)?;
+    Ok(())
+}
+
+pub async fn copy_file(src: &Path, dest: &Path) -> Result<()> {
+    #[wasm_bindgen(module = "fs/promises")]
+    extern "C" {
+        #[wasm_bindgen(js_name = copyFile, catch)]
+        async fn copy_file(src: &str, dest: &str) -> Result<(), JsValue>;
+    }
+
+    copy_file(src.to_str().unwrap(), dest.to_str().unwrap())
+        .await
+        .map_err(JsError)?;
+    Ok(())
+}
+
 pub async fn read_file(path: &Path) -> Result<Vec<u8>> {
     #[wasm_bindgen(module = "fs/promises")]
     extern "C" {
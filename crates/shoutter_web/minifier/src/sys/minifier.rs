@@ -89,7 +89,9 @@ pub async fn js(js: &str) -> Result<String> {
         async fn minify(js: &str, option: Object) -> Result<JsValue, JsValue>;
     }
 
-    let res = minify(js, js_minifier_option()).await.map_err(JsError)?;
+    let res = minify(js, js_minifier_option())
+        .await
+        .map_err(|e| JsError(e).located())?;
     let res = Reflect::get(&res, &JsValue::from("code"))
         .expect("minify response should have `code` key")
         .as_string()
@@ -97,3 +99,53 @@ pub async fn js(js: &str) -> Result<String> {
 
     Ok(res)
 }
+
+/// Same as [`js`] but writes a source map, composed with the one of the input if any.
+/// `file_name` names the source when there is none.
+pub async fn js_with_source_map(
+    js: &str,
+    file_name: &str,
+    input_map: Option<&sourcemap::SourceMap>,
+) -> Result<(String, sourcemap::SourceMap)> {
+    #[wasm_bindgen(module = "terser")]
+    extern "C" {
+        #[wasm_bindgen(catch)]
+        async fn minify(js: &str, option: Object) -> Result<JsValue, JsValue>;
+    }
+
+    let source_map = match input_map {
+        Some(input_map) => {
+            let mut content = vec![];
+            input_map.to_writer(&mut content)?;
+            object! {
+                content: String::from_utf8(content)?,
+            }
+        }
+        None => object! {},
+    };
+    let option = js_minifier_option();
+    Reflect::set(
+        &option,
+        &JsValue::from("sourceMap"),
+        &JsValue::from(source_map),
+    )
+    .expect("setting property on the object should never fail.");
+
+    let res = minify(js, option).await.map_err(|e| JsError(e).located())?;
+    let code = Reflect::get(&res, &JsValue::from("code"))
+        .expect("minify response should have `code` key")
+        .as_string()
+        .expect("minifyResponse.code should be string");
+    let map = Reflect::get(&res, &JsValue::from("map"))
+        .expect("minify response should have `map` key")
+        .as_string()
+        .expect("minifyResponse.map should be string");
+
+    let mut map = sourcemap::SourceMap::from_slice(map.as_bytes())?;
+    // terser calls a source given as a string `0`
+    if input_map.is_none() {
+        map.set_source(0, file_name);
+    }
+
+    Ok((code, map))
+}
@@ -1,7 +1,11 @@
-pub mod brotli;
+pub mod env;
 pub mod fs;
 pub mod minifier;
-use wasm_bindgen::JsValue;
+pub mod process;
+use js_sys::Reflect;
+use wasm_bindgen::{JsCast, JsValue};
+
+use crate::error::{LocatedError, SourceLocation};
 
 #[derive(Debug)]
 struct JsError(JsValue);
@@ -9,8 +13,37 @@ struct JsError(JsValue);
 unsafe impl Send for JsError {}
 unsafe impl Sync for JsError {}
 
+impl JsError {
+    // parse errors thrown by terser carry `line` and `col`.
+    fn located(self) -> anyhow::Error {
+        let get = |key: &str| Reflect::get(&self.0, &JsValue::from(key)).ok()?.as_f64();
+        match (get("line"), get("col")) {
+            (Some(line), Some(column)) => LocatedError {
+                location: SourceLocation {
+                    line: line as usize,
+                    column: column as usize,
+                },
+                message: self.to_string(),
+            }
+            .into(),
+            _ => self.into(),
+        }
+    }
+}
+
 impl std::fmt::Display for JsError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(e) = self.0.dyn_ref::<js_sys::Error>() {
+            return write!(
+                f,
+                "{}: {}",
+                String::from(e.name()),
+                String::from(e.message())
+            );
+        }
+        if let Some(s) = self.0.as_string() {
+            return f.write_str(&s);
+        }
         <Self as std::fmt::Debug>::fmt(self, f)
     }
 }
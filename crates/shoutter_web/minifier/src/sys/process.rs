@@ -0,0 +1,22 @@
+use js_sys::Reflect;
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
+
+// node exits with this code once the event loop is drained.
+pub fn set_exit_code(code: i32) {
+    let process = Reflect::get(&js_sys::global(), &JsValue::from("process"))
+        .expect("`process` should be defined on node");
+    Reflect::set(&process, &JsValue::from("exitCode"), &JsValue::from(code))
+        .expect("setting property on the object should never fail.");
+}
+
+// milliseconds from a monotonic clock, for timing passes.
+pub fn now() -> f64 {
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = performance, js_name = now)]
+        fn performance_now() -> f64;
+    }
+
+    performance_now()
+}
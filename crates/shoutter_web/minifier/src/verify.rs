@@ -0,0 +1,379 @@
+// checks that a rewritten wasm module is still valid and is the same program as the input.
+
+use anyhow::{ensure, Context, Result};
+
+/// Everything of a module that symbol minification must leave untouched.
+/// Names are left out on purpose.
+#[derive(Default, PartialEq)]
+struct Shape {
+    types: Vec<String>,
+    imports: Vec<String>,
+    functions: Vec<u32>,
+    tables: Vec<(String, Option<Vec<u8>>)>,
+    memories: Vec<String>,
+    tags: Vec<String>,
+    globals: Vec<(String, Vec<u8>)>,
+    exports: Vec<(String, u32)>,
+    start: Option<u32>,
+    elements: Vec<(String, String, Vec<Vec<u8>>)>,
+    data_count: Option<u32>,
+    data: Vec<(String, Vec<u8>)>,
+    code: Vec<Vec<u8>>,
+    unknown: Vec<(u8, Vec<u8>)>,
+}
+
+fn const_expr_bytes(expr: &wasmparser::ConstExpr) -> Result<Vec<u8>> {
+    let mut reader = expr.get_binary_reader();
+    Ok(reader.read_bytes(reader.bytes_remaining())?.to_vec())
+}
+
+impl Shape {
+    fn new(wasm: &[u8]) -> Result<Shape> {
+        let mut shape = Shape::default();
+        for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+            match payload? {
+                wasmparser::Payload::TypeSection(section) => {
+                    for ty in section {
+                        shape.types.push(format!("{:?}", ty?));
+                    }
+                }
+                wasmparser::Payload::ImportSection(section) => {
+                    for import in section {
+                        shape.imports.push(format!("{:?}", import?.ty));
+                    }
+                }
+                wasmparser::Payload::FunctionSection(section) => {
+                    for function in section {
+                        shape.functions.push(function?);
+                    }
+                }
+                wasmparser::Payload::TableSection(section) => {
+                    for table in section {
+                        let table = table?;
+                        let init = match table.init {
+                            wasmparser::TableInit::RefNull => None,
+                            wasmparser::TableInit::Expr(init) => Some(const_expr_bytes(&init)?),
+                        };
+                        shape.tables.push((format!("{:?}", table.ty), init));
+                    }
+                }
+                wasmparser::Payload::MemorySection(section) => {
+                    for memory in section {
+                        shape.memories.push(format!("{:?}", memory?));
+                    }
+                }
+                wasmparser::Payload::TagSection(section) => {
+                    for tag in section {
+                        shape.tags.push(format!("{:?}", tag?));
+                    }
+                }
+                wasmparser::Payload::GlobalSection(section) => {
+                    for global in section {
+                        let global = global?;
+                        shape.globals.push((
+                            format!("{:?}", global.ty),
+                            const_expr_bytes(&global.init_expr)?,
+                        ));
+                    }
+                }
+                wasmparser::Payload::ExportSection(section) => {
+                    for export in section {
+                        let export = export?;
+                        shape
+                            .exports
+                            .push((format!("{:?}", export.kind), export.index));
+                    }
+                }
+                wasmparser::Payload::StartSection { func, .. } => shape.start = Some(func),
+                wasmparser::Payload::ElementSection(section) => {
+                    for element in section {
+                        let element = element?;
+                        let kind = match element.kind {
+                            wasmparser::ElementKind::Passive => "passive".to_owned(),
+                            wasmparser::ElementKind::Declared => "declared".to_owned(),
+                            wasmparser::ElementKind::Active {
+                                table_index,
+                                offset_expr,
+                            } => format!(
+                                "active {table_index} {:?}",
+                                const_expr_bytes(&offset_expr)?
+                            ),
+                        };
+                        let items = match element.items {
+                            wasmparser::ElementItems::Functions(f) => f
+                                .into_iter()
+                                .map(|x| Ok(x?.to_le_bytes().to_vec()))
+                                .collect::<Result<_>>()?,
+                            wasmparser::ElementItems::Expressions(e) => e
+                                .into_iter()
+                                .map(|x| const_expr_bytes(&x?))
+                                .collect::<Result<_>>()?,
+                        };
+                        shape
+                            .elements
+                            .push((kind, format!("{:?}", element.ty), items));
+                    }
+                }
+                wasmparser::Payload::DataCountSection { count, .. } => {
+                    shape.data_count = Some(count)
+                }
+                wasmparser::Payload::DataSection(section) => {
+                    for data in section {
+                        let data = data?;
+                        let kind = match data.kind {
+                            wasmparser::DataKind::Passive => "passive".to_owned(),
+                            wasmparser::DataKind::Active {
+                                memory_index,
+                                offset_expr,
+                            } => format!(
+                                "active {memory_index} {:?}",
+                                const_expr_bytes(&offset_expr)?
+                            ),
+                        };
+                        shape.data.push((kind, data.data.to_vec()));
+                    }
+                }
+                wasmparser::Payload::CodeSectionEntry(body) => {
+                    let mut reader = body.get_binary_reader();
+                    shape
+                        .code
+                        .push(reader.read_bytes(reader.bytes_remaining())?.to_vec());
+                }
+                wasmparser::Payload::UnknownSection { id, contents, .. } => {
+                    shape.unknown.push((id, contents.to_vec()))
+                }
+                _ => {}
+            }
+        }
+        Ok(shape)
+    }
+}
+
+// features rustc may emit on wasm32 besides the default ones, given the target features.
+fn validator() -> wasmparser::Validator {
+    wasmparser::Validator::new_with_features(wasmparser::WasmFeatures {
+        simd: true,
+        relaxed_simd: true,
+        threads: true,
+        tail_call: true,
+        exceptions: true,
+        extended_const: true,
+        ..Default::default()
+    })
+}
+
+/// Validates `minified` and compares its structure against `original` section by section.
+/// Function bodies are compared only when `same_code` is set; otherwise just their number.
+/// Data segments and their count are compared only when `same_data` is set.
+pub fn verify(original: &[u8], minified: &[u8], same_code: bool, same_data: bool) -> Result<()> {
+    validator()
+        .validate_all(original)
+        .context("input module is invalid")?;
+    validator()
+        .validate_all(minified)
+        .context("minified module is invalid")?;
+
+    let (before, after) = (Shape::new(original)?, Shape::new(minified)?);
+    let mut mismatches = vec![];
+    macro_rules! compare {
+        ($($field:ident),*$(,)?) => {
+            $(if before.$field != after.$field {
+                mismatches.push(stringify!($field));
+            })*
+        };
+    }
+    compare!(types, imports, functions, tables, memories, tags, globals, start, elements, unknown,);
+    if same_data && (before.data != after.data || before.data_count != after.data_count) {
+        mismatches.push("data");
+    }
+    if before.code.len() != after.code.len() || (same_code && before.code != after.code) {
+        mismatches.push("code");
+    }
+    // unused exports may be dropped
+    if !after.exports.iter().all(|x| before.exports.contains(x)) {
+        mismatches.push("exports");
+    }
+    ensure!(
+        mismatches.is_empty(),
+        "minified module differs from the input in: {}",
+        mismatches.join(", "),
+    );
+    Ok(())
+}
+
+// shaped after wasm-bindgen output: `wbg` imports, exported memory and allocator,
+// a function table, a stack pointer global and static data.
+#[cfg(test)]
+pub fn wasm_bindgen_sample() -> Vec<u8> {
+    use wasm_encoder::{
+        CodeSection, ConstExpr, CustomSection, DataSection, ElementSection, Elements, EntityType,
+        ExportKind, ExportSection, Function, FunctionSection, GlobalSection, GlobalType,
+        ImportSection, Instruction, MemorySection, MemoryType, Module, RefType, TableSection,
+        TableType, TypeSection, ValType,
+    };
+
+    let mut module = Module::new();
+
+    let mut types = TypeSection::new();
+    types.function([ValType::I32], []);
+    types.function([ValType::I32], [ValType::I32]);
+    types.function([], []);
+    module.section(&types);
+
+    let mut imports = ImportSection::new();
+    imports.import("wbg", "__wbg_alert_5b3b0d1f", EntityType::Function(0));
+    imports.import("wbg", "__wbindgen_throw", EntityType::Function(0));
+    imports.import("wbg", "__wbg_unused_9abc", EntityType::Function(2));
+    module.section(&imports);
+
+    let mut functions = FunctionSection::new();
+    functions.function(0).function(1).function(2);
+    module.section(&functions);
+
+    let mut tables = TableSection::new();
+    tables.table(TableType {
+        element_type: RefType::FUNCREF,
+        minimum: 2,
+        maximum: Some(2),
+    });
+    module.section(&tables);
+
+    let mut memories = MemorySection::new();
+    memories.memory(MemoryType {
+        minimum: 17,
+        maximum: None,
+        memory64: false,
+        shared: false,
+    });
+    module.section(&memories);
+
+    let mut globals = GlobalSection::new();
+    globals.global(
+        GlobalType {
+            val_type: ValType::I32,
+            mutable: true,
+        },
+        &ConstExpr::i32_const(1048576),
+    );
+    module.section(&globals);
+
+    let mut exports = ExportSection::new();
+    exports.export("memory", ExportKind::Memory, 0);
+    exports.export("greet", ExportKind::Func, 3);
+    exports.export("__wbindgen_malloc", ExportKind::Func, 4);
+    module.section(&exports);
+
+    let mut elements = ElementSection::new();
+    elements.active(
+        None,
+        &ConstExpr::i32_const(1),
+        RefType::FUNCREF,
+        Elements::Functions(&[5]),
+    );
+    module.section(&elements);
+
+    let mut code = CodeSection::new();
+    let mut greet = Function::new([]);
+    greet
+        .instruction(&Instruction::LocalGet(0))
+        .instruction(&Instruction::Call(0))
+        .instruction(&Instruction::End);
+    code.function(&greet);
+    let mut malloc = Function::new([(1, ValType::I32)]);
+    malloc
+        .instruction(&Instruction::GlobalGet(0))
+        .instruction(&Instruction::LocalGet(0))
+        .instruction(&Instruction::I32Sub)
+        .instruction(&Instruction::LocalTee(1))
+        .instruction(&Instruction::GlobalSet(0))
+        .instruction(&Instruction::LocalGet(1))
+        .instruction(&Instruction::End);
+    code.function(&malloc);
+    let mut panic = Function::new([]);
+    panic
+        .instruction(&Instruction::I32Const(1048576))
+        .instruction(&Instruction::Call(1))
+        .instruction(&Instruction::Unreachable)
+        .instruction(&Instruction::End);
+    code.function(&panic);
+    module.section(&code);
+
+    let mut data = DataSection::new();
+    data.active(
+        0,
+        &ConstExpr::i32_const(1048576),
+        b"hello, world\0src/lib.rs\0".iter().copied(),
+    );
+    module.section(&data);
+
+    module.section(&CustomSection {
+        name: "producers".into(),
+        data: b"\0".as_slice().into(),
+    });
+
+    module.finish()
+}
+
+#[test]
+fn verify_minified_sample() {
+    let original = wasm_bindgen_sample();
+    let strip = crate::symbol::SymbolOptions {
+        strip_sections: vec!["*".to_owned()],
+        ..Default::default()
+    };
+    let mut minified = original.clone();
+    let crate::symbol::Minified {
+        symbols, sections, ..
+    } = crate::symbol::minify_symbol(&mut minified, None, &strip).unwrap();
+
+    assert!(minified.len() < original.len());
+    assert_eq!(symbols.exports.len(), 3);
+    assert_eq!(sections[0].name, "producers");
+    verify(&original, &minified, true, true).unwrap();
+
+    let options = crate::symbol::SymbolOptions {
+        optimize_code: true,
+        ..Default::default()
+    };
+    let mut optimized = original.clone();
+    crate::symbol::minify_symbol(&mut optimized, None, &options).unwrap();
+    verify(&original, &optimized, false, true).unwrap();
+
+    // a module with a different body must be rejected.
+    let mut broken = wasm_bindgen_sample();
+    let at = broken
+        .windows(4)
+        .position(|x| x == [0x20, 0x00, 0x10, 0x00])
+        .unwrap();
+    broken[at + 3] = 0x01; // `local.get 0; call 0` => `local.get 0; call 1`
+    let err = verify(&original, &broken, true, true)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("code"), "{err}");
+}
+
+// `*_bg.wasm` of the app, copied by `just corpus`. it isn't committed, so the test passes
+// without it.
+#[test]
+fn verify_minified_corpus() {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src/corpus");
+    let paths = std::fs::read_dir(&dir)
+        .into_iter()
+        .flatten()
+        .map(|x| x.unwrap().path())
+        .filter(|x| x.to_string_lossy().ends_with("_bg.wasm"))
+        .collect::<Vec<_>>();
+    if paths.is_empty() {
+        eprintln!("no `*_bg.wasm` in {}, run `just corpus`", dir.display());
+        return;
+    }
+    for path in paths {
+        let original = std::fs::read(&path).unwrap();
+        let mut minified = original.clone();
+        crate::symbol::minify_symbol(&mut minified, None, &Default::default())
+            .with_context(|| path.display().to_string())
+            .unwrap();
+        assert!(minified.len() < original.len(), "{}", path.display());
+        verify(&original, &minified, true, true).unwrap();
+    }
+}
@@ -1,5 +1,7 @@
 mod pages;
 
+use stylist::manager::StyleManager;
+use stylist::yew::ManagerProvider;
 use yew::prelude::*;
 use yew_router::{BrowserRouter, Switch};
 
@@ -7,10 +9,23 @@ use crate::pages::{switch, Route};
 
 #[function_component]
 fn App() -> Html {
+    // stylist names classes `<prefix>-<id>` at runtime, out of reach of the minifier,
+    // so the prefix is kept short here instead of the default `stylist`.
+    let manager = use_memo(
+        |_| {
+            StyleManager::builder()
+                .prefix("s".into())
+                .build()
+                .expect("failed to create style manager")
+        },
+        (),
+    );
     html! {
-        <BrowserRouter>
-            <Switch<Route> render={switch} />
-        </BrowserRouter>
+        <ManagerProvider manager={(*manager).clone()}>
+            <BrowserRouter>
+                <Switch<Route> render={switch} />
+            </BrowserRouter>
+        </ManagerProvider>
     }
 }
 